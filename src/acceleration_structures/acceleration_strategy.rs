@@ -0,0 +1,27 @@
+use crate::acceleration_structures::BVH;
+use crate::acceleration_structures::ObjectList;
+use crate::acceleration_structures::DEFAULT_LEAF_SIZE;
+use crate::traits::AccelerationStructure;
+use crate::traits::RenderObject;
+
+// Which acceleration structure a scene builds over its bounded objects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccelerationStrategy {
+    None,
+    BVH { max_leaf_size: usize },
+}
+
+impl Default for AccelerationStrategy {
+    fn default() -> Self {
+        AccelerationStrategy::BVH { max_leaf_size: DEFAULT_LEAF_SIZE }
+    }
+}
+
+impl AccelerationStrategy {
+    pub fn build(&self, render_objects: &[Box<dyn RenderObject>], indices: Vec<usize>) -> Box<dyn AccelerationStructure> {
+        match *self {
+            AccelerationStrategy::BVH { max_leaf_size } if !indices.is_empty() => Box::new(BVH::with_leaf_size(render_objects, indices, max_leaf_size)),
+            _ => Box::new(ObjectList::new(indices)),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::acceleration_structures::AccelerationStrategy;
+use crate::traits::AccelerationStructure;
+use crate::traits::RenderObject;
+use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Ray;
+use crate::maths::Vector3;
+use crate::shapes::Bounds;
+use std::sync::Arc;
+
+// A group of render objects with its own acceleration structure, for example the triangles of one mesh.
+// Shared between instances, which place it in the scene
+pub struct Aggregate {
+    render_objects: Vec<Box<dyn RenderObject>>,
+    acceleration_structure: Box<dyn AccelerationStructure>,
+    bounds: (Vector3, Vector3),
+}
+
+impl Aggregate {
+    pub fn new(render_objects: Vec<Box<dyn RenderObject>>, strategy: AccelerationStrategy) -> Arc<Aggregate> {
+        assert!(!render_objects.is_empty(), "Aggregate needs at least one render object");
+        let mut min = Vector3 (f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = Vector3 (f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for object in &render_objects {
+            match object.bounds() {
+                Bounds::BoundingBox(object_min, object_max) => {
+                    min = Vector3::min(&min, &object_min);
+                    max = Vector3::max(&max, &object_max);
+                },
+                Bounds::Full => panic!("Aggregate cannot contain unbounded render objects"),
+            }
+        }
+
+        let acceleration_structure = strategy.build(&render_objects, (0..render_objects.len()).collect());
+        Arc::new(Aggregate { render_objects, acceleration_structure, bounds: (min, max) })
+    }
+}
+
+impl RenderObject for Aggregate {
+    fn intersect(&self, ray: &Ray) -> Option<IntersectionPayload> {
+        self.acceleration_structure.intersect(&self.render_objects, ray).map(|(_, payload)| payload)
+    }
+
+    fn occluded(&self, ray: &Ray) -> bool {
+        self.acceleration_structure.occluded(&self.render_objects, ray)
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::BoundingBox(self.bounds.0, self.bounds.1)
+    }
+
+    // The objects may use different materials, so there is no single id. Hits report the material of the
+    // object hit, and the scene never looks this up as aggregates are not samplable
+    fn material_id(&self) -> usize {
+        usize::MAX
+    }
+}
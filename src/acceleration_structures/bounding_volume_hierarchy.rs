@@ -1,88 +1,412 @@
+use crate::traits::AccelerationStructure;
 use crate::traits::RenderObject;
 use crate::shapes::Bounds;
-use std::cmp::Ordering;
-use rand::Rng;
 use crate::maths::Vector3;
 use crate::data_structures::IntersectionPayload;
 use crate::data_structures::Ray;
 
-pub enum BVH {
-    Node(Box<BVH>, Box<BVH>, Bounds),
-    Shape(Box<dyn RenderObject>, Bounds)
+pub const DEFAULT_LEAF_SIZE: usize = 4;
+
+// Number of buckets centroids are sorted into when searching for a split
+const BIN_COUNT: usize = 16;
+
+// Relative costs of visiting a node and of intersecting a render object, used by the surface area heuristic
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+
+// Deepest a tree may grow, which bounds the traversal stack
+const MAX_DEPTH: usize = 64;
+
+// Nodes are stored depth first, so an interior node's first child directly follows it
+pub struct BVH {
+    nodes: Vec<LinearNode>,
+    // Indices of render objects owned by the scene, each leaf refers to a contiguous range
+    object_indices: Vec<usize>,
 }
 
-impl BVH {
-    pub fn intersect(&self, ray: &Ray) -> Option<IntersectionPayload> {
-        match self {
-            // If intersection the Shape type, check bounds then intersect enclosed render_object
-            BVH::Shape(render_object, bounds) => {
-                if !bounds.intersect(ray) { return None; }
-                render_object.intersect(ray)
-            },
-            BVH::Node(left, right, bounds) => {
-                if !bounds.intersect(ray) { return None; }
-                match (left.intersect(ray), right.intersect(ray)) {
-                    (None, None) => None,
-                    (Some(payload), None) | (None, Some(payload)) => {
-                        if payload.distance > 1e-3 { Some(payload) } else { None }
-                    },
-                    (Some(payload_a), Some(payload_b)) => {
-                        if payload_a.distance < payload_b.distance { return Some(payload_a); }
-                        Some(payload_b)
+struct LinearNode {
+    bounds: Aabb,
+    // First object for a leaf, index of the second child for an interior node
+    offset: usize,
+    // Number of objects, zero for an interior node
+    count: usize,
+    // Axis the children were split along, used to visit the nearer child first
+    axis: usize,
+}
+
+// Measures of tree quality. The SAH cost is the expected cost of a random ray that hits the root
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BvhStatistics {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub max_depth: usize,
+    pub max_leaf_size: usize,
+    pub average_leaf_size: f64,
+    pub sah_cost: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Aabb {
+    min: Vector3,
+    max: Vector3,
+}
+
+impl Aabb {
+    fn empty() -> Aabb {
+        Aabb { min: Vector3 (f64::INFINITY, f64::INFINITY, f64::INFINITY), max: Vector3 (f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY) }
+    }
+
+    fn union(&self, other: &Aabb) -> Aabb {
+        Aabb { min: Vector3::min(&self.min, &other.min), max: Vector3::max(&self.max, &other.max) }
+    }
+
+    fn grow(&self, point: Vector3) -> Aabb {
+        Aabb { min: Vector3::min(&self.min, &point), max: Vector3::max(&self.max, &point) }
+    }
+
+    fn surface_area(&self) -> f64 {
+        if self.min.0 > self.max.0 { return 0.0; }
+        let d = self.max - self.min;
+        2.0 * (d.0 * d.1 + d.1 * d.2 + d.2 * d.0)
+    }
+
+    // Distance at which the ray enters the box, if it does within its range
+    fn hit_distance(&self, ray: &Ray, inverse_direction: Vector3) -> Option<f64> {
+        let origin = ray.origin;
+        let mut t0 = ray.t_min;
+        let mut t1 = ray.t_max;
+        for axis in 0..3 {
+            let mut near = (self.min[axis] - origin[axis]) * inverse_direction[axis];
+            let mut far = (self.max[axis] - origin[axis]) * inverse_direction[axis];
+            if near > far { std::mem::swap(&mut near, &mut far); }
+
+            // Widen slightly so rounding cannot make a grazing ray miss the box
+            t0 = t0.max(near);
+            t1 = t1.min(far * (1.0 + 4.0 * f64::EPSILON));
+            if t0 > t1 { return None; }
+        }
+        Some(t0)
+    }
+}
+
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vector3,
+}
+
+struct Split {
+    cost: f64,
+    axis: usize,
+    bin: usize,
+}
+
+impl AccelerationStructure for BVH {
+    fn intersect(&self, render_objects: &[Box<dyn RenderObject>], ray: &Ray) -> Option<(usize, IntersectionPayload)> {
+        let inverse_direction = Vector3 (1.0 / ray.direction.0, 1.0 / ray.direction.1, 1.0 / ray.direction.2);
+        // Each hit shortens the ray, culling nodes and objects further away
+        let mut ray = *ray;
+        let mut record_payload: Option<(usize, IntersectionPayload)> = None;
+
+        let mut stack = [0usize; MAX_DEPTH];
+        let mut stack_size = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node.bounds.hit_distance(&ray, inverse_direction).is_some() {
+                if node.count > 0 {
+                    for &index in &self.object_indices[node.offset..node.offset + node.count] {
+                        if let Some(payload) = render_objects[index].intersect(&ray) {
+                            ray.t_max = payload.distance;
+                            record_payload = Some((index, payload));
+                        }
                     }
+                } else {
+                    // Visit the child on the near side of the split first, so hits there cull the far child
+                    let (near, far) = if ray.direction[node.axis] < 0.0 { (node.offset, current + 1) } else { (current + 1, node.offset) };
+                    stack[stack_size] = far;
+                    stack_size += 1;
+                    current = near;
+                    continue;
                 }
             }
+
+            if stack_size == 0 { break; }
+            stack_size -= 1;
+            current = stack[stack_size];
         }
+        record_payload
     }
 
-    pub fn new(mut render_objects: Vec<Box<dyn RenderObject>>) -> BVH {
-        if render_objects.len() == 1 {
-            let render_object = render_objects.remove(0);
-            let bounds = render_object.bounds();
-            BVH::Shape(render_object, bounds)
-        } else {
-            let axis = rand::thread_rng().gen_range(0..3);
-    
-            render_objects.sort_by(|a, b| {
-                let box_a = a.bounds();
-                let box_b = b.bounds();
-
-                match (box_a, box_b) {
-                    (Bounds::Full, Bounds::Full) => Ordering::Equal,
-                    (Bounds::Full, _) => Ordering::Greater,
-                    (_, Bounds::Full) => Ordering::Less,
-                    (Bounds::BoundingBox(min_a, _), Bounds::BoundingBox(min_b, _)) => min_a[axis].partial_cmp(&min_b[axis]).unwrap() 
+    fn occluded(&self, render_objects: &[Box<dyn RenderObject>], ray: &Ray) -> bool {
+        let inverse_direction = Vector3 (1.0 / ray.direction.0, 1.0 / ray.direction.1, 1.0 / ray.direction.2);
+
+        let mut stack = [0usize; MAX_DEPTH];
+        let mut stack_size = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node.bounds.hit_distance(ray, inverse_direction).is_some() {
+                if node.count > 0 {
+                    let objects = &self.object_indices[node.offset..node.offset + node.count];
+                    if objects.iter().any(|&index| render_objects[index].occluded(ray)) { return true; }
+                } else {
+                    // Any hit will do, so the order children are visited in does not matter
+                    stack[stack_size] = node.offset;
+                    stack_size += 1;
+                    current += 1;
+                    continue;
                 }
-            });
+            }
 
-            let mid = render_objects.len() / 2;
-            let right_objects = render_objects.split_off(mid);
-            let left = Box::new(BVH::new(render_objects));
-            let right = Box::new(BVH::new(right_objects));
-            let bounds = BVH::surrounding_box(left.bounds(), right.bounds());
+            if stack_size == 0 { return false; }
+            stack_size -= 1;
+            current = stack[stack_size];
+        }
+    }
+}
+
+impl BVH {
+    // Build over the given indices into render_objects, which must all have finite bounds
+    pub fn new(render_objects: &[Box<dyn RenderObject>], indices: Vec<usize>) -> BVH {
+        BVH::with_leaf_size(render_objects, indices, DEFAULT_LEAF_SIZE)
+    }
 
-            BVH::Node(left, right, bounds)
+    // Binned surface area heuristic build. Leaves hold at most max_leaf_size objects, and the tree only
+    // depends on the order of indices so repeated builds are identical
+    pub fn with_leaf_size(render_objects: &[Box<dyn RenderObject>], indices: Vec<usize>, max_leaf_size: usize) -> BVH {
+        assert!(!indices.is_empty(), "BVH needs at least one render object");
+        let mut items: Vec<BuildItem> = indices.into_iter().map(|index| {
+            let bounds = match render_objects[index].bounds() {
+                Bounds::BoundingBox(min, max) => Aabb { min, max },
+                Bounds::Full => panic!("BVH cannot contain unbounded render objects"),
+            };
+            BuildItem { index, bounds, centroid: (bounds.min + bounds.max) * 0.5 }
+        }).collect();
+
+        let mut bvh = BVH { nodes: Vec::new(), object_indices: Vec::with_capacity(items.len()) };
+        bvh.build(&mut items, max_leaf_size.max(1), 1);
+        bvh
+    }
+
+    // Appends the subtree for items to the node array, returning the index of its root
+    fn build(&mut self, items: &mut [BuildItem], max_leaf_size: usize, depth: usize) -> usize {
+        let bounds = items.iter().fold(Aabb::empty(), |bounds, item| bounds.union(&item.bounds));
+        let centroid_bounds = items.iter().fold(Aabb::empty(), |bounds, item| bounds.grow(item.centroid));
+        let index = self.nodes.len();
+
+        let split = BVH::find_split(items, &bounds, &centroid_bounds);
+        let leaf_cost = INTERSECTION_COST * items.len() as f64;
+        let small_enough = items.len() <= max_leaf_size && split.as_ref().is_none_or(|split| leaf_cost <= split.cost);
+        if items.len() == 1 || small_enough || depth == MAX_DEPTH {
+            self.nodes.push(LinearNode { bounds, offset: self.object_indices.len(), count: items.len(), axis: 0 });
+            self.object_indices.extend(items.iter().map(|item| item.index));
+            return index;
         }
+
+        // With every centroid in the same place no plane separates them, so fall back to halving
+        let (mid, axis) = match split {
+            Some(split) => (BVH::partition(items, |item| BVH::bin(item, &centroid_bounds, split.axis) <= split.bin), split.axis),
+            None => (items.len() / 2, 0),
+        };
+        self.nodes.push(LinearNode { bounds, offset: 0, count: 0, axis });
+
+        let (left_items, right_items) = items.split_at_mut(mid);
+        self.build(left_items, max_leaf_size, depth + 1);
+        self.nodes[index].offset = self.build(right_items, max_leaf_size, depth + 1);
+        index
     }
 
-    fn bounds(&self) -> &Bounds {
-        match self {
-            BVH::Node(_, _, bounds) => &bounds,
-            BVH::Shape(_, bounds) => &bounds,
+    fn find_split(items: &[BuildItem], bounds: &Aabb, centroid_bounds: &Aabb) -> Option<Split> {
+        let parent_area = bounds.surface_area().max(f64::MIN_POSITIVE);
+        let mut best: Option<Split> = None;
+
+        for axis in 0..3 {
+            if centroid_bounds.max[axis] <= centroid_bounds.min[axis] { continue; }
+
+            let mut bins = [(Aabb::empty(), 0usize); BIN_COUNT];
+            for item in items {
+                let bin = &mut bins[BVH::bin(item, centroid_bounds, axis)];
+                bin.0 = bin.0.union(&item.bounds);
+                bin.1 += 1;
+            }
+
+            // Sweep from the right so each candidate plane can then be costed in one pass from the left
+            let mut right_costs = [0.0; BIN_COUNT];
+            let mut right = (Aabb::empty(), 0);
+            for bin in (1..BIN_COUNT).rev() {
+                right = (right.0.union(&bins[bin].0), right.1 + bins[bin].1);
+                right_costs[bin - 1] = if right.1 == 0 { f64::INFINITY } else { right.0.surface_area() * right.1 as f64 };
+            }
+
+            let mut left = (Aabb::empty(), 0);
+            for bin in 0..BIN_COUNT - 1 {
+                left = (left.0.union(&bins[bin].0), left.1 + bins[bin].1);
+                if left.1 == 0 || right_costs[bin].is_infinite() { continue; }
+
+                let cost = TRAVERSAL_COST + INTERSECTION_COST * (left.0.surface_area() * left.1 as f64 + right_costs[bin]) / parent_area;
+                if best.as_ref().is_none_or(|best| cost < best.cost) {
+                    best = Some(Split { cost, axis, bin });
+                }
+            }
         }
+        best
     }
 
-    fn surrounding_box(box_a: &Bounds, box_b: &Bounds) -> Bounds {
-        match (box_a, box_b) {
-            (Bounds::Full, _) => Bounds::Full,
-            (_, Bounds::Full) => Bounds::Full,
-            (Bounds::BoundingBox(min_a, max_a), Bounds::BoundingBox(min_b, max_b)) => {
-                let min = Vector3::min(&min_a, &min_b);
-                let max = Vector3::max(&max_a, &max_b);
+    fn bin(item: &BuildItem, centroid_bounds: &Aabb, axis: usize) -> usize {
+        let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+        let offset = (item.centroid[axis] - centroid_bounds.min[axis]) / extent;
+        ((offset * BIN_COUNT as f64) as usize).min(BIN_COUNT - 1)
+    }
 
-                Bounds::BoundingBox(min, max)
+    // Moves the items matching predicate to the front, returning how many there are
+    fn partition(items: &mut [BuildItem], predicate: impl Fn(&BuildItem) -> bool) -> usize {
+        let mut mid = 0;
+        for i in 0..items.len() {
+            if predicate(&items[i]) {
+                items.swap(i, mid);
+                mid += 1;
             }
         }
+        mid
+    }
+
+    pub fn statistics(&self) -> BvhStatistics {
+        let mut statistics = BvhStatistics::default();
+        let root_area = self.nodes[0].bounds.surface_area().max(f64::MIN_POSITIVE);
+        self.accumulate_statistics(0, &mut statistics, root_area, 1);
+
+        statistics.average_leaf_size = self.object_indices.len() as f64 / statistics.leaf_count as f64;
+        statistics
+    }
+
+    fn accumulate_statistics(&self, index: usize, statistics: &mut BvhStatistics, root_area: f64, depth: usize) {
+        let node = &self.nodes[index];
+        let area_ratio = node.bounds.surface_area() / root_area;
+        statistics.node_count += 1;
+        statistics.max_depth = statistics.max_depth.max(depth);
+
+        if node.count > 0 {
+            statistics.leaf_count += 1;
+            statistics.max_leaf_size = statistics.max_leaf_size.max(node.count);
+            statistics.sah_cost += INTERSECTION_COST * area_ratio * node.count as f64;
+        } else {
+            statistics.sah_cost += TRAVERSAL_COST * area_ratio;
+            self.accumulate_statistics(index + 1, statistics, root_area, depth + 1);
+            self.accumulate_statistics(node.offset, statistics, root_area, depth + 1);
+        }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acceleration_structures::ObjectList;
+    use crate::maths::random;
+    use crate::maths::reseed;
+    use crate::shapes::Sphere;
+    use crate::shapes::TriangleMesh;
+
+    fn spheres(count: usize) -> Vec<Box<dyn RenderObject>> {
+        reseed(3);
+        (0..count)
+            .map(|i| Sphere::new(Vector3 (random::<f64>() * 10.0, random::<f64>() * 10.0, random::<f64>() * 10.0), 0.5, i) as Box<dyn RenderObject>)
+            .collect()
+    }
+
+    #[test]
+    fn matches_object_list() {
+        let render_objects = spheres(50);
+        let indices: Vec<usize> = (0..render_objects.len()).collect();
+        let bvh = BVH::new(&render_objects, indices.clone());
+        let list = ObjectList::new(indices);
+
+        for _ in 0..500 {
+            let origin = Vector3 (random::<f64>() * 10.0, random::<f64>() * 10.0, -5.0);
+            let direction = Vector3 (random::<f64>() - 0.5, random::<f64>() - 0.5, 1.0).normalise();
+            let ray = Ray::new(origin, direction);
+            let expected = list.intersect(&render_objects, &ray).map(|(index, payload)| (index, payload.material_id));
+            assert_eq!(bvh.intersect(&render_objects, &ray).map(|(index, payload)| (index, payload.material_id)), expected);
+        }
+    }
+
+    #[test]
+    fn occlusion_matches_closest_hit() {
+        let render_objects = spheres(50);
+        let bvh = BVH::new(&render_objects, (0..render_objects.len()).collect());
+
+        for _ in 0..500 {
+            let origin = Vector3 (random::<f64>() * 10.0, random::<f64>() * 10.0, -5.0);
+            let direction = Vector3 (random::<f64>() - 0.5, random::<f64>() - 0.5, 1.0).normalise();
+            let mut ray = Ray::new(origin, direction);
+            ray.t_max = random::<f64>() * 20.0;
+            assert_eq!(bvh.occluded(&render_objects, &ray), bvh.intersect(&render_objects, &ray).is_some());
+        }
+    }
+
+    #[test]
+    fn matches_object_list_on_mesh() {
+        // A bumpy grid of triangles, where many boxes overlap along each ray
+        let size = 20;
+        let positions = (0..=size).flat_map(|y| (0..=size).map(move |x| Vector3 (x as f64, y as f64, ((x * 7 + y * 3) % 5) as f64 * 0.3))).collect();
+        let indices: Vec<[u32; 3]> = (0..size).flat_map(|y| (0..size).flat_map(move |x| {
+            let corner = (y * (size + 1) + x) as u32;
+            let row = (size + 1) as u32;
+            [[corner, corner + 1, corner + row + 1], [corner, corner + row + 1, corner + row]]
+        })).collect();
+        let material_ids = (0..indices.len()).collect();
+        let render_objects = TriangleMesh::triangles(&TriangleMesh::new(positions, vec![], vec![], vec![], indices, material_ids));
+
+        let all: Vec<usize> = (0..render_objects.len()).collect();
+        let bvh = BVH::new(&render_objects, all.clone());
+        let list = ObjectList::new(all);
+        reseed(5);
+        for _ in 0..500 {
+            let origin = Vector3 (random::<f64>() * 30.0 - 5.0, random::<f64>() * 30.0 - 5.0, 5.0);
+            let direction = Vector3 (random::<f64>() - 0.5, random::<f64>() - 0.5, -1.0).normalise();
+            let ray = Ray::new(origin, direction);
+            let expected = list.intersect(&render_objects, &ray).map(|(index, payload)| (index, payload.material_id));
+            assert_eq!(bvh.intersect(&render_objects, &ray).map(|(index, payload)| (index, payload.material_id)), expected);
+        }
+    }
+
+    #[test]
+    fn builds_are_deterministic() {
+        let render_objects = spheres(200);
+        let first = BVH::new(&render_objects, (0..200).collect()).statistics();
+        let second = BVH::new(&render_objects, (0..200).collect()).statistics();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn respects_leaf_size() {
+        let render_objects = spheres(200);
+        for leaf_size in [1, 2, 8] {
+            let statistics = BVH::with_leaf_size(&render_objects, (0..200).collect(), leaf_size).statistics();
+            assert!(statistics.max_leaf_size <= leaf_size);
+            assert!((statistics.average_leaf_size * statistics.leaf_count as f64 - 200.0).abs() < 1e-9);
+            assert_eq!(statistics.node_count, 2 * statistics.leaf_count - 1);
+        }
+    }
+
+    #[test]
+    fn splits_separate_clusters() {
+        // Two far apart clusters should be divided at the root, leaving one leaf for each
+        let render_objects: Vec<Box<dyn RenderObject>> = (0..8)
+            .map(|i| Sphere::new(Vector3 (if i < 4 { 0.0 } else { 100.0 }, i as f64 * 0.01, 0.0), 0.5, i) as Box<dyn RenderObject>)
+            .collect();
+        let bvh = BVH::new(&render_objects, (0..8).collect());
+        let statistics = bvh.statistics();
+        assert_eq!(statistics.leaf_count, 2);
+        assert_eq!(statistics.max_depth, 2);
+        assert_eq!(bvh.nodes[1].count, 4);
+        assert_eq!(bvh.object_indices, (0..8).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn identical_centroids() {
+        let render_objects: Vec<Box<dyn RenderObject>> = (0..10).map(|i| Sphere::new(Vector3 (0.0, 0.0, 0.0), 1.0, i) as Box<dyn RenderObject>).collect();
+        let statistics = BVH::with_leaf_size(&render_objects, (0..10).collect(), 3).statistics();
+        assert!(statistics.max_leaf_size <= 3);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::traits::RenderObject;
+use crate::traits::Transformable;
+use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Ray;
+use crate::maths::Matrix4x4;
+use crate::maths::Vector3;
+use crate::maths::gamma;
+use crate::shapes::Bounds;
+use std::sync::Arc;
+
+// Places a shared object, usually an Aggregate, in the scene with an object to world transform. Rays are
+// moved into object space instead of transforming the object's geometry
+pub struct Instance {
+    object: Arc<dyn RenderObject>,
+    object_to_world: Matrix4x4,
+    world_to_object: Matrix4x4,
+    bounds: (Vector3, Vector3),
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn RenderObject>, object_to_world: Matrix4x4) -> Box<Instance> {
+        let world_to_object = object_to_world.inverse().expect("Instance transform must be invertible");
+        let (min, max) = match object.bounds() {
+            Bounds::BoundingBox(min, max) => (min, max),
+            Bounds::Full => panic!("Instance cannot contain an unbounded render object"),
+        };
+
+        // The world bounds enclose all eight transformed corners of the object's bounds
+        let mut world_min = Vector3 (f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut world_max = Vector3 (f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for corner in 0..8 {
+            let point = Vector3 (
+                if corner & 1 == 0 { min.0 } else { max.0 },
+                if corner & 2 == 0 { min.1 } else { max.1 },
+                if corner & 4 == 0 { min.2 } else { max.2 }
+            ).transform(&object_to_world, true);
+            world_min = Vector3::min(&world_min, &point);
+            world_max = Vector3::max(&world_max, &point);
+        }
+
+        Box::new(Instance { object, object_to_world, world_to_object, bounds: (world_min, world_max) })
+    }
+
+    // The ray in object space along with how much longer its direction became. Shapes expect unit
+    // directions, so the direction is normalised and distances along the ray scaled to match
+    fn object_ray(&self, ray: &Ray) -> Option<(Ray, f64)> {
+        let object_ray = ray.transform(&self.world_to_object, true);
+        let scale = object_ray.direction.magnitude();
+        if scale == 0.0 { return None; }
+        Some((Ray { origin: object_ray.origin, direction: object_ray.direction / scale, t_min: ray.t_min * scale, t_max: ray.t_max * scale }, scale))
+    }
+}
+
+impl RenderObject for Instance {
+    fn intersect(&self, ray: &Ray) -> Option<IntersectionPayload> {
+        let (object_ray, scale) = self.object_ray(ray)?;
+        let payload = self.object.intersect(&object_ray)?;
+        let distance = payload.distance / scale;
+
+        // Normals transform by the inverse transpose to stay perpendicular under non-uniform scaling
+        let normal_transform = self.world_to_object.transpose();
+        let normal = normal_transform.transform(&payload.normal, false).normalise();
+        let geometric_normal = normal_transform.transform(&payload.geometric_normal, false).normalise();
+
+        // Carry the object space error through the transform, plus the rounding of the transform itself
+        let position = payload.position.transform(&self.object_to_world, true);
+        let absolute = |v: Vector3| Vector3 (
+            (0..3).map(|j| (self.object_to_world[(0, j)] * v[j]).abs()).sum::<f64>(),
+            (0..3).map(|j| (self.object_to_world[(1, j)] * v[j]).abs()).sum::<f64>(),
+            (0..3).map(|j| (self.object_to_world[(2, j)] * v[j]).abs()).sum::<f64>()
+        );
+        let translation = Vector3 (self.object_to_world[(0, 3)], self.object_to_world[(1, 3)], self.object_to_world[(2, 3)]).abs();
+        let error = absolute(payload.error) * (1.0 + gamma(3)) + (absolute(payload.position) + translation) * gamma(3);
+        Some(IntersectionPayload { position, distance, normal, geometric_normal, error, ..payload })
+    }
+
+    fn occluded(&self, ray: &Ray) -> bool {
+        self.object_ray(ray).is_some_and(|(object_ray, _)| self.object.occluded(&object_ray))
+    }
+
+    fn bounds(&self) -> Bounds {
+        Bounds::BoundingBox(self.bounds.0, self.bounds.1)
+    }
+
+    fn material_id(&self) -> usize {
+        self.object.material_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::acceleration_structures::AccelerationStrategy;
+    use crate::acceleration_structures::Aggregate;
+    use crate::shapes::Sphere;
+
+    fn unit_spheres() -> Arc<dyn RenderObject> {
+        Aggregate::new(vec![Sphere::new(Vector3 (0.0, 0.0, 0.0), 1.0, 2), Sphere::new(Vector3 (3.0, 0.0, 0.0), 1.0, 3)], AccelerationStrategy::default())
+    }
+
+    #[test]
+    fn translated_and_scaled() {
+        let transform = Matrix4x4::translation(Vector3 (5.0, 0.0, 0.0)) * Matrix4x4::scale(Vector3 (2.0, 2.0, 2.0));
+        let instance = Instance::new(unit_spheres(), transform);
+
+        let payload = instance.intersect(&Ray::new(Vector3 (5.0, 0.0, 10.0), Vector3 (0.0, 0.0, -1.0))).unwrap();
+        assert!((payload.distance - 8.0).abs() < 1e-9);
+        assert!((payload.position - Vector3 (5.0, 0.0, 2.0)).magnitude() < 1e-9);
+        assert!((payload.normal - Vector3 (0.0, 0.0, 1.0)).magnitude() < 1e-9);
+        assert_eq!(payload.material_id, 2);
+
+        let payload = instance.intersect(&Ray::new(Vector3 (11.0, 0.0, 10.0), Vector3 (0.0, 0.0, -1.0))).unwrap();
+        assert_eq!(payload.material_id, 3);
+
+        match instance.bounds() {
+            Bounds::BoundingBox(min, max) => {
+                assert!((min - Vector3 (3.0, -2.0, -2.0)).magnitude() < 1e-9);
+                assert!((max - Vector3 (13.0, 2.0, 2.0)).magnitude() < 1e-9);
+            },
+            Bounds::Full => panic!("instance bounds should be finite"),
+        }
+    }
+
+    #[test]
+    fn non_uniform_scale_normals() {
+        // Squashing the sphere into an ellipsoid tilts the normal towards the short axis
+        let instance = Instance::new(unit_spheres(), Matrix4x4::scale(Vector3 (1.0, 0.25, 1.0)));
+        let direction = Vector3 (0.0, -1.0, -1.0).normalise();
+        let payload = instance.intersect(&Ray::new(Vector3 (0.0, 5.0, 5.0), direction)).unwrap();
+
+        assert!((payload.position - Vector3 (0.0, 5.0, 5.0) - direction * payload.distance).magnitude() < 1e-9);
+        let p = payload.position;
+        assert!((p.0 * p.0 + 16.0 * p.1 * p.1 + p.2 * p.2 - 1.0).abs() < 1e-9);
+        let expected = Vector3 (p.0, 16.0 * p.1, p.2).normalise();
+        assert!((payload.normal - expected).magnitude() < 1e-9);
+    }
+}
@@ -1,3 +1,13 @@
 mod bounding_volume_hierarchy;
+mod object_list;
+mod acceleration_strategy;
+mod aggregate;
+mod instance;
 
 pub use bounding_volume_hierarchy::BVH;
+pub use bounding_volume_hierarchy::BvhStatistics;
+pub use bounding_volume_hierarchy::DEFAULT_LEAF_SIZE;
+pub use object_list::ObjectList;
+pub use acceleration_strategy::AccelerationStrategy;
+pub use aggregate::Aggregate;
+pub use instance::Instance;
@@ -0,0 +1,40 @@
+use crate::traits::AccelerationStructure;
+use crate::traits::RenderObject;
+use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Ray;
+
+// Tests every object in turn, used when no acceleration is wanted
+pub struct ObjectList {
+    indices: Vec<usize>,
+}
+
+impl ObjectList {
+    pub fn new(indices: Vec<usize>) -> ObjectList {
+        ObjectList { indices }
+    }
+}
+
+impl AccelerationStructure for ObjectList {
+    fn intersect(&self, render_objects: &[Box<dyn RenderObject>], ray: &Ray) -> Option<(usize, IntersectionPayload)> {
+        // Each hit shortens the ray, so objects further away are rejected early
+        let mut ray = *ray;
+        let mut record_payload: Option<(usize, IntersectionPayload)> = None;
+
+        for &index in &self.indices {
+            let object = &render_objects[index];
+            if object.bounds().intersect(&ray).is_none() { continue; }
+            if let Some(payload) = object.intersect(&ray) {
+                ray.t_max = payload.distance;
+                record_payload = Some((index, payload));
+            }
+        }
+        record_payload
+    }
+
+    fn occluded(&self, render_objects: &[Box<dyn RenderObject>], ray: &Ray) -> bool {
+        self.indices.iter().any(|&index| {
+            let object = &render_objects[index];
+            object.bounds().intersect(ray).is_some() && object.occluded(ray)
+        })
+    }
+}
@@ -38,12 +38,23 @@ impl Div<f64> for Color {
     }
 }
 
+// sRGB transfer function, from linear light to the encoded value
+fn srgb_encode(linear: f64) -> f64 {
+    if linear <= 0.0031308 { 12.92 * linear } else { 1.055 * linear.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn srgb_decode(encoded: f64) -> f64 {
+    if encoded <= 0.04045 { encoded / 12.92 } else { ((encoded + 0.055) / 1.055).powf(2.4) }
+}
+
 impl Color {
+    // Packs linear RGB as sRGB encoded bytes, alpha is stored linearly
     pub fn to_bytes(&self) -> u32 {
-        let r = ((self.0.sqrt() * 255.0).round().min(255.0) as u32) << 24;
-        let g = ((self.1.sqrt() * 255.0).round().min(255.0) as u32) << 16;
-        let b = ((self.2.sqrt() * 255.0).round().min(255.0) as u32) << 8;
-        let a = ((self.3.sqrt() * 255.0).round().min(255.0) as u32) << 0;
+        let quantise = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u32;
+        let r = quantise(srgb_encode(self.0.max(0.0))) << 24;
+        let g = quantise(srgb_encode(self.1.max(0.0))) << 16;
+        let b = quantise(srgb_encode(self.2.max(0.0))) << 8;
+        let a = quantise(self.3);
         r + g + b + a
     }
 
@@ -51,8 +62,13 @@ impl Color {
         let r = ((byte & 0xff000000) >> 24) as f64 / 255.0;
         let g = ((byte & 0x00ff0000) >> 16) as f64 / 255.0;
         let b = ((byte & 0x0000ff00) >> 8) as f64 / 255.0;
-        let a = ((byte & 0x000000ff) >> 0) as f64 / 255.0;
-        Color(r, g, b, a) 
+        let a = (byte & 0x000000ff) as f64 / 255.0;
+        Color::from_srgb(r, g, b, a)
+    }
+
+    // Linear colour from sRGB encoded channels in 0..1, alpha is taken as already linear
+    pub fn from_srgb(r: f64, g: f64, b: f64, a: f64) -> Color {
+        Color (srgb_decode(r), srgb_decode(g), srgb_decode(b), a)
     }
 
     pub fn normalise(&self) -> Color {
@@ -71,6 +87,18 @@ mod test {
         assert_eq!(bytes, 0xffffffff)
     }
 
+    #[test]
+    fn srgb_transfer() {
+        // Middle grey in linear light is encoded well above half way
+        assert_eq!(Color (0.5, 0.0, 0.22, 0.5).to_bytes(), 0xbc_00_81_80);
+        assert!((srgb_encode(0.0031308) - 12.92 * 0.0031308).abs() < 1e-7);
+        assert!((srgb_decode(srgb_encode(0.7)) - 0.7).abs() < 1e-12);
+
+        for byte in [0x00_10_80_ffu32, 0x7f_c8_02_40] {
+            assert_eq!(Color::from_bytes(byte).to_bytes(), byte);
+        }
+    }
+
     #[test]
     fn from_bytes() {
         let bytes = 0xffffffff;
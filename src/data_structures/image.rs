@@ -1,21 +1,35 @@
 use crate::data_structures::Color;
+use crate::data_structures::ToneMapper;
+use crate::data_structures::exposure_scale;
+use crate::image_formats::write_bmp;
+use crate::image_formats::write_png;
+use crate::image_formats::write_ppm;
+use crate::image_formats::PpmEncoding;
+use crate::image_formats::ImageError;
+use crate::image_formats::read_bmp;
+use crate::image_formats::read_ppm;
+use crate::image_formats::read_pfm;
+use crate::image_formats::write_pfm;
+use crate::image_formats::write_hdr;
+use crate::image_formats::write_exr;
+use crate::image_formats::ExrPixelType;
+use crate::image_formats::ExrCompression;
+use std::fs;
 use std::fs::File;
+use std::io::BufWriter;
 use std::io::Write;
+use std::path::Path;
 
-static BMP_HEADER: [u8; 54] = [
-    b'B', b'M', 0, 0, 0, 0, 0, 0, 0, 0, 54, 0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 24,
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-];
-
+// Pixels hold linear radiance, which is only tone mapped and quantised when the image is exported
 pub struct Image {
-    pixels: Vec<u32>,
+    pixels: Vec<Color>,
     pub width: usize,
     pub height: usize,
 }
 
 impl Image {
     pub fn new(width: usize, height: usize) -> Image {
-        let pixels: Vec<u32> = vec![0xffffffff; width * height];
+        let pixels: Vec<Color> = vec![Color (1.0, 1.0, 1.0, 1.0); width * height];
         Image {
             pixels,
             width,
@@ -24,41 +38,55 @@ impl Image {
     }
 
     pub fn set_pixel(&mut self, x: usize, y: usize, color: &Color) {
-        let bytes = color.to_bytes();
-        self.pixels[y * self.width + x] = bytes;
+        self.pixels[y * self.width + x] = *color;
     }
 
     pub fn get_pixel(&self, x: usize, y: usize) -> Color {
-        let bytes = self.pixels[y * self.width + x];
-        Color::from_bytes(bytes)
+        self.pixels[y * self.width + x]
     }
 
-    pub fn save(&self, filepath: &str) {
-        let mut bytes = BMP_HEADER.to_vec();
+    // 8 bit sRGB pixels for display, after adjusting exposure by the given number of stops and tone mapping
+    pub fn quantise(&self, exposure: f64, tone_mapper: ToneMapper) -> Vec<u32> {
+        let exposure = exposure_scale(exposure);
 
-        // Set the width and height values in the header
-        bytes[0x12..0x16].copy_from_slice(&(self.width as u32).to_le_bytes());
-        bytes[0x16..0x1A].copy_from_slice(&(self.height as u32).to_le_bytes());
+        // Scaling a Color resets its alpha, so exposure is applied to the colour channels alone
+        self.pixels.iter()
+            .map(|&color| tone_mapper.apply(Color (color.0 * exposure, color.1 * exposure, color.2 * exposure, color.3)).to_bytes())
+            .collect()
+    }
 
-        // Preallocate memory to optimise allocations
-        bytes.reserve_exact(self.width * self.height * 3);
+    // Reads a BMP, PPM or PFM file, chosen by the file extension. 8 bit formats are converted from sRGB to
+    // linear values
+    pub fn load(filepath: &str) -> Result<Image, ImageError> {
+        let extension = Path::new(filepath).extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        let read = match extension.as_str() {
+            "bmp" => read_bmp,
+            "ppm" => read_ppm,
+            "pfm" => read_pfm,
+            _ => return Err(ImageError::UnsupportedFormat(extension)),
+        };
+        read(&fs::read(filepath)?)
+    }
 
-        // Write the pixel data to the file in the right order
-        for row in self.pixels.chunks(self.width).rev() {
-            for pixel in row {
-                bytes.push(((pixel & 0x0000ff00) >> 8) as u8);
-                bytes.push(((pixel & 0x00ff0000) >> 16) as u8);
-                bytes.push(((pixel & 0xff000000) >> 24) as u8);
-            }
+    // The format is chosen by the file extension. HDR formats store the linear values as they are,
+    // exposure and tone mapping only apply to 8 bit formats
+    pub fn save(&self, filepath: &str, exposure: f64, tone_mapper: ToneMapper) -> Result<(), ImageError> {
+        let extension = Path::new(filepath).extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        if !["bmp", "png", "ppm", "pfm", "hdr", "exr"].contains(&extension.as_str()) {
+            return Err(ImageError::UnsupportedFormat(extension));
         }
 
-        // Set the file length in the header
-        let len = bytes.len() as u32;
-        bytes[0x02..0x06].copy_from_slice(&len.to_le_bytes());
-
-        // Write to disk
-        let mut file = File::create(filepath).unwrap();
-        file.write_all(&bytes).unwrap();
+        let mut writer = BufWriter::new(File::create(filepath)?);
+        match extension.as_str() {
+            "bmp" => write_bmp(self, &mut writer, exposure, tone_mapper)?,
+            "png" => write_png(self, &mut writer, exposure, tone_mapper)?,
+            "ppm" => write_ppm(self, &mut writer, exposure, tone_mapper, PpmEncoding::Binary)?,
+            "pfm" => write_pfm(self, &mut writer)?,
+            "hdr" => write_hdr(self, &mut writer)?,
+            _ => write_exr(self, &mut writer, ExrPixelType::default(), ExrCompression::default())?,
+        }
+        writer.flush()?;
+        Ok(())
     }
 }
 
@@ -71,4 +99,27 @@ mod tests {
         let image = Image::new(64, 64);
         assert!(image.pixels.len() == 64 * 64)
     }
+
+    #[test]
+    fn keeps_high_dynamic_range() {
+        let mut image = Image::new(2, 1);
+        image.set_pixel(0, 0, &Color (4.0, 0.5, 0.0, 1.0));
+        image.set_pixel(1, 0, &Color (16.0, 0.5, 0.0, 1.0));
+        assert_eq!(image.get_pixel(1, 0), Color (16.0, 0.5, 0.0, 1.0));
+
+        // Clamping loses the difference between the two, exposure and tone mapping recover it
+        let clamped = image.quantise(0.0, ToneMapper::Clamp);
+        assert_eq!(clamped[0] >> 24, clamped[1] >> 24);
+        let mapped = image.quantise(-2.0, ToneMapper::Reinhard);
+        assert!(mapped[0] >> 24 < mapped[1] >> 24);
+    }
+
+    #[test]
+    fn save_reports_errors() {
+        let image = Image::new(2, 2);
+        assert!(matches!(image.save("render.jpg", 0.0, ToneMapper::Clamp), Err(ImageError::UnsupportedFormat(extension)) if extension == "jpg"));
+
+        let missing_directory = std::env::temp_dir().join("fe-o-missing-directory").join("render.png");
+        assert!(matches!(image.save(missing_directory.to_str().unwrap(), 0.0, ToneMapper::Clamp), Err(ImageError::Io(_))));
+    }
 }
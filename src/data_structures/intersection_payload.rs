@@ -1,10 +1,107 @@
 use crate::maths::Vector3;
+use crate::data_structures::Ray;
+use crate::data_structures::Color;
 
 pub struct IntersectionPayload {
     pub position: Vector3,
     pub distance: f64,
+    // Shading normal, which may be interpolated
     pub normal: Vector3,
     pub material_id: usize,
     pub u: f64,
-    pub v: f64
+    pub v: f64,
+    // Normal of the actual surface, used to leave it without hitting it again
+    pub geometric_normal: Vector3,
+    // Absolute bound on the rounding error in each component of position
+    pub error: Vector3,
+    // Interpolated vertex colour on meshes that have them, white elsewhere. Materials multiply it into their albedo
+    pub vertex_color: Color,
+}
+
+impl IntersectionPayload {
+    // Ray leaving the surface in direction. The origin is pushed along the geometric normal just past the
+    // error bounds of position, so the ray cannot hit the surface it starts on
+    pub fn spawn_ray(&self, direction: Vector3) -> Ray {
+        let normal = self.geometric_normal;
+        let distance = normal.abs() * self.error;
+        let offset = if direction * normal >= 0.0 { normal * distance } else { normal * -distance };
+
+        // Rounding the sum could undo part of the offset, so step to the next float away from the surface
+        let round_away = |value: f64, offset: f64| {
+            if offset > 0.0 { value.next_up() } else if offset < 0.0 { value.next_down() } else { value }
+        };
+        let origin = self.position + offset;
+        let origin = Vector3 (round_away(origin.0, offset.0), round_away(origin.1, offset.1), round_away(origin.2, offset.2));
+        Ray::new(origin, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::maths::Vector3;
+    use crate::maths::random;
+    use crate::maths::reseed;
+    use crate::shapes::Sphere;
+    use crate::shapes::TriangleMesh;
+    use crate::shapes::XZRect;
+    use crate::traits::RenderObject;
+    use crate::data_structures::Ray;
+
+    // Directions within a hair of the surface on the side given by sign
+    fn grazing_direction(normal: Vector3, sign: f64) -> Vector3 {
+        let tangent = Vector3::cross(&normal, &Vector3::random_unit()).normalise();
+        let elevation = 10f64.powf(-2.0 - random::<f64>() * 6.0);
+        (tangent + normal * (sign * elevation)).normalise()
+    }
+
+    // Hit the object with rays from origin_of, then leave it at grazing angles on both sides
+    fn assert_no_self_intersection(object: &dyn RenderObject, origin_of: impl Fn() -> (Vector3, Vector3), convex: bool) {
+        reseed(11);
+        for _ in 0..2000 {
+            let (origin, target) = origin_of();
+            let Some(payload) = object.intersect(&Ray::new(origin, (target - origin).normalise())) else { continue; };
+
+            let outside = payload.spawn_ray(grazing_direction(payload.geometric_normal, 1.0));
+            assert!(object.intersect(&outside).is_none(), "ray leaving {:?} hit the surface again", payload.position);
+
+            // Only a flat surface can be left on the inside without meeting it again
+            if !convex {
+                let inside = payload.spawn_ray(grazing_direction(payload.geometric_normal, -1.0));
+                assert!(object.intersect(&inside).is_none(), "ray entering at {:?} hit the surface again", payload.position);
+            }
+        }
+    }
+
+    #[test]
+    fn sphere_far_from_origin() {
+        let center = Vector3 (1.0e4, -2.0e4, 3.0e4);
+        let sphere = Sphere::new(center, 100.0, 0);
+        assert_no_self_intersection(sphere.as_ref(), || {
+            // Aim close to the silhouette, where hits are most grazing
+            let target = center + Vector3::random_unit() * 99.0;
+            (center + Vector3 (0.0, 0.0, 500.0), target)
+        }, true);
+    }
+
+    #[test]
+    fn tilted_mesh_far_from_origin() {
+        let offset = Vector3 (5.0e3, 1.0e3, -7.0e3);
+        let positions = vec![offset, offset + Vector3 (30.0, 1.0, 3.0), offset + Vector3 (2.0, 20.0, 17.0)];
+        let normals = vec![Vector3 (0.0, 0.0, 1.0), Vector3 (1.0, 0.0, 0.0), Vector3 (0.0, 1.0, 0.0)];
+        let mesh = TriangleMesh::new(positions, normals, vec![], vec![], vec![[0, 1, 2]], vec![0]);
+        let triangle = TriangleMesh::triangles(&mesh).remove(0);
+        assert_no_self_intersection(triangle.as_ref(), || {
+            let target = offset + Vector3 (8.0, 6.0, 5.0) + Vector3::random_unit();
+            (target + Vector3::random_unit() * 50.0, target)
+        }, false);
+    }
+
+    #[test]
+    fn rect() {
+        let rect = XZRect::new(-1.0e3, -1.0e3, 1.0e3, 1.0e3, 333.3, 0);
+        assert_no_self_intersection(rect.as_ref(), || {
+            let target = Vector3 (random::<f64>() * 1.0e3, 333.3, random::<f64>() * 1.0e3);
+            (Vector3 (-900.0, 333.3 + 0.01, -900.0), target)
+        }, false);
+    }
 }
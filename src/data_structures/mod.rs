@@ -4,6 +4,7 @@ mod ray;
 mod intersection_payload;
 mod scene;
 mod scatter_payload;
+mod tone_mapper;
 
 pub use image::Image;
 pub use color::Color;
@@ -11,3 +12,5 @@ pub use ray::Ray;
 pub use intersection_payload::IntersectionPayload;
 pub use scene::Scene;
 pub use scatter_payload::ScatterPayload;
+pub use tone_mapper::ToneMapper;
+pub use tone_mapper::exposure_scale;
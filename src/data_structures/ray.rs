@@ -2,28 +2,36 @@ use crate::maths::Vector3;
 use crate::traits::Transformable;
 use crate::maths::Matrix4x4;
 
+// Only hits at distances strictly between t_min and t_max count
 #[derive(Debug, Copy, Clone)]
 pub struct Ray {
     pub origin: Vector3,
-    pub direction: Vector3
+    pub direction: Vector3,
+    pub t_min: f64,
+    pub t_max: f64,
 }
 
 impl Ray {
     pub fn at(&self, t: f64) -> Vector3 {
         self.origin + t * self.direction
     }
+
+    pub fn in_range(&self, t: f64) -> bool {
+        t > self.t_min && t < self.t_max
+    }
 }
 
 impl Transformable for Ray {
+    // The direction is not renormalised, so distances along the ray and its range are unchanged
     fn transform(&self, frame: &Matrix4x4, translate: bool) -> Self {
         let origin = self.origin.transform(frame, translate);
         let direction = self.direction.transform(frame, false);
-        Ray { origin, direction }
+        Ray { origin, direction, ..*self }
     }
 }
 
 impl Ray {
     pub fn new(origin: Vector3, direction: Vector3) -> Ray {
-        Ray { origin, direction }
+        Ray { origin, direction, t_min: 0.0, t_max: f64::INFINITY }
     }
 }
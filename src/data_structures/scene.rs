@@ -3,78 +3,355 @@ use crate::data_structures::Color;
 use crate::data_structures::Ray;
 use crate::traits::Material;
 use crate::samplers::RenderObjectSampler;
-use crate::samplers::MixtureSampler;
 use crate::traits::Sampler;
-use crate::samplers::CosineSampler;
+use crate::traits::AccelerationStructure;
+use crate::acceleration_structures::AccelerationStrategy;
 use crate::data_structures::IntersectionPayload;
+use crate::shapes::Bounds;
+use crate::maths::random;
+use crate::maths::Vector3;
+use crate::RenderSettings;
+use crate::MisHeuristic;
+
+// Relative distance by which shadow rays stop short of the point sampled on a light
+const SHADOW_EPSILON: f64 = 1e-4;
 
 pub struct Scene {
     render_objects: Vec<Box<dyn RenderObject>>,
     materials: Vec<Box<dyn Material>>,
-    lights: Vec<Box<dyn RenderObject>>,
+    // Built over the objects with finite bounds
+    acceleration_structure: Box<dyn AccelerationStructure>,
+    // Indices of the objects with Bounds::Full, which are tested separately
+    unbounded_objects: Vec<usize>,
+    // Indices of the render objects with an emissive material
+    lights: Vec<usize>,
+    // Cumulative selection probabilities for the lights, weighted by emitted power
+    light_cdf: Vec<f64>,
+    // For each render object, its position in lights if it is one
+    light_slots: Vec<Option<usize>>,
     background_color: Color,
-
-    max_depth: usize
 }
 
 impl Scene {
 
-    pub fn get_color(&self, ray: Ray) -> Color {
-        self.trace(ray, 0)
+    pub fn get_color(&self, ray: Ray, settings: &RenderSettings) -> Color {
+        self.trace(ray, settings.max_depth, settings.mis_heuristic)
     }
 
-    fn trace(&self, ray: Ray, depth: usize) -> Color {
-        if depth > self.max_depth { return Color (0.0, 0.0, 0.0, 1.0); }
-
-        let payload_option = self.get_intersect(&ray);
-        match payload_option {
-            None => self.background_color,
-            Some(payload) => {
-                let material = &self.materials[payload.material_id];
-                let light_emmited = material.emmission(&payload);
+    fn trace(&self, mut ray: Ray, max_depth: usize, heuristic: MisHeuristic) -> Color {
+        let mut color = Color (0.0, 0.0, 0.0, 1.0);
+        let mut throughput = Color (1.0, 1.0, 1.0, 1.0);
+        // Pdf of the material sample that produced the current ray, None for camera rays
+        let mut material_pdf: Option<f64> = None;
 
-                let pdf_a = RenderObjectSampler::new(payload.position, &self.lights[0]);
-                let pdf_b = CosineSampler::new(payload.normal);
-                let mix_pdf = pdf_a; // MixturePDF::new(&pdf_a, &pdf_b);
+        for depth in 0..=max_depth {
+            let (object_index, payload) = match self.get_intersect(&ray) {
+                None => return color + throughput * self.background_color,
+                Some(hit) => hit,
+            };
+            let material = &self.materials[payload.material_id];
 
-                let outgoing_direction = mix_pdf.generate();
-                let outgoing_ray = Ray { origin: payload.position, direction: outgoing_direction };
-                let pdf_value = mix_pdf.value(outgoing_direction);
+            // Emission found by material sampling is weighted against the chance light sampling would have found it
+            let light_emmited = material.emmission(&payload, ray.direction);
+            let is_emissive = light_emmited.0 != 0.0 || light_emmited.1 != 0.0 || light_emmited.2 != 0.0;
+            if is_emissive {
+                let weight = match material_pdf {
+                    None => 1.0,
+                    Some(pdf) => heuristic.weight(pdf, self.light_pdf(&ray, object_index)),
+                };
+                color = color + throughput * light_emmited * weight;
+            }
+            if depth == max_depth { break; }
 
-                let light_sampled = self.trace(outgoing_ray, depth + 1);
-                let light_transmitted = material.transmission(&payload, ray.direction, outgoing_direction);
+            let Some(scatter) = material.scatter(&payload, ray.direction) else { break; };
 
-                if pdf_value != 0.0 { 
-                    light_emmited + (light_transmitted * light_sampled) / pdf_value
-                } else {
-                    light_emmited
-                }
+            // Specular materials scatter into a single direction that light sampling can never hit
+            if scatter.is_specular {
+                let outgoing_direction = scatter.pdf.generate();
+                throughput = throughput * scatter.attenuation;
+                material_pdf = None;
+                ray = payload.spawn_ray(outgoing_direction);
+                continue;
             }
+
+            color = color + throughput * self.sample_direct(&payload, material.as_ref(), scatter.pdf.as_ref(), ray.direction, heuristic);
+
+            let outgoing_direction = scatter.pdf.generate();
+            let pdf_value = scatter.pdf.value(outgoing_direction);
+            if pdf_value == 0.0 { break; }
+
+            let light_transmitted = material.transmission(&payload, ray.direction, outgoing_direction);
+            throughput = throughput * light_transmitted / pdf_value;
+            material_pdf = Some(pdf_value);
+            ray = payload.spawn_ray(outgoing_direction);
         }
+        color
+    }
+
+    // Estimate the light arriving directly from one randomly chosen light, using a shadow ray to test visibility
+    fn sample_direct(&self, payload: &IntersectionPayload, material: &dyn Material, material_sampler: &dyn Sampler, incoming_direction: Vector3, heuristic: MisHeuristic) -> Color {
+        let black = Color (0.0, 0.0, 0.0, 1.0);
+        let Some((light, selection_pdf)) = self.choose_light() else { return black; };
+
+        let light_sampler = RenderObjectSampler::new(payload.position, light);
+        let outgoing_direction = light_sampler.generate();
+        let shadow_ray = payload.spawn_ray(outgoing_direction);
+
+        let Some(light_payload) = light.intersect(&shadow_ray) else { return black; };
+        let pdf_value = selection_pdf * light_sampler.value(outgoing_direction);
+        if pdf_value == 0.0 { return black; }
+
+        // Stop just short of the light so it does not shadow itself
+        let shadow_ray = Ray { t_max: light_payload.distance * (1.0 - SHADOW_EPSILON), ..shadow_ray };
+        if self.occluded(&shadow_ray) { return black; }
+
+        let light_emmited = self.materials[light_payload.material_id].emmission(&light_payload, outgoing_direction);
+        let light_transmitted = material.transmission(payload, incoming_direction, outgoing_direction);
+        let weight = heuristic.weight(pdf_value, material_sampler.value(outgoing_direction));
+        (light_transmitted * light_emmited) * (weight / pdf_value)
+    }
+
+    // Pdf with which light sampling would have chosen the direction of ray, given it first hits the render
+    // object at object_index
+    fn light_pdf(&self, ray: &Ray, object_index: usize) -> f64 {
+        let Some(slot) = self.light_slots[object_index] else { return 0.0; };
+        let total = self.light_cdf[self.light_cdf.len() - 1];
+        let previous = if slot == 0 { 0.0 } else { self.light_cdf[slot - 1] };
+        (self.light_cdf[slot] - previous) / total * self.render_objects[object_index].pdf_value(*ray)
+    }
+
+    fn choose_light(&self) -> Option<(&dyn RenderObject, f64)> {
+        let total = *self.light_cdf.last()?;
+        let target = random::<f64>() * total;
+        let index = self.light_cdf.partition_point(|&c| c <= target).min(self.lights.len() - 1);
+        let previous = if index == 0 { 0.0 } else { self.light_cdf[index - 1] };
+        Some((self.render_objects[self.lights[index]].as_ref(), (self.light_cdf[index] - previous) / total))
+    }
+
+    fn occluded(&self, ray: &Ray) -> bool {
+        self.acceleration_structure.occluded(&self.render_objects, ray)
+            || self.unbounded_objects.iter().any(|&index| self.render_objects[index].occluded(ray))
     }
 
-    fn get_intersect(&self, ray: &Ray) -> Option<IntersectionPayload> {
-        let mut record_payload = None;
-        
-        for object in &self.render_objects {
-            let bounds = object.bounds();
-            if !bounds.intersect(ray) { continue; }
-            match object.intersect(ray) {
-                None => (),
-                Some(payload) => {
-                    record_payload = match record_payload {
-                        None => Some(payload),
-                        Some(record) => {
-                            if payload.distance < record.distance { Some(payload) } else { Some(record) }
-                        }
-                    }
-                }
+    // Closest hit, along with the index of the render object that was hit
+    fn get_intersect(&self, ray: &Ray) -> Option<(usize, IntersectionPayload)> {
+        let mut ray = *ray;
+        let mut record_payload = self.acceleration_structure.intersect(&self.render_objects, &ray);
+        if let Some((_, payload)) = &record_payload { ray.t_max = payload.distance; }
+
+        for &index in &self.unbounded_objects {
+            if let Some(payload) = self.render_objects[index].intersect(&ray) {
+                ray.t_max = payload.distance;
+                record_payload = Some((index, payload));
             }
         }
         record_payload
     }
 
-    pub fn new(render_objects: Vec<Box<dyn RenderObject>>, materials: Vec<Box<dyn Material>>, lights: Vec<Box<dyn RenderObject>>, background_color: Color, max_depth: usize) -> Scene {
-        Scene { render_objects, materials, lights, background_color, max_depth }
+    // Every bounded object with an emissive material that can be sampled is registered as a light.
+    // Instances and aggregates cannot be sampled, so emissive surfaces inside them are only found by
+    // material sampling, which is unbiased but noisy
+    pub fn new(render_objects: Vec<Box<dyn RenderObject>>, materials: Vec<Box<dyn Material>>, background_color: Color) -> Scene {
+        Scene::with_acceleration(render_objects, materials, background_color, AccelerationStrategy::default())
+    }
+
+    pub fn with_acceleration(render_objects: Vec<Box<dyn RenderObject>>, materials: Vec<Box<dyn Material>>, background_color: Color, strategy: AccelerationStrategy) -> Scene {
+        let mut lights = Vec::new();
+        let mut light_cdf = Vec::new();
+        let mut light_slots = vec![None; render_objects.len()];
+        let mut total_power = 0.0;
+        for (index, object) in render_objects.iter().enumerate() {
+            if !object.is_samplable() || matches!(object.bounds(), Bounds::Full) { continue; }
+            let power = materials[object.material_id()].emitted_power();
+            if power <= 0.0 { continue; }
+
+            total_power += power * object.area();
+            light_slots[index] = Some(lights.len());
+            lights.push(index);
+            light_cdf.push(total_power);
+        }
+
+        let (unbounded_objects, bounded_objects): (Vec<usize>, Vec<usize>) = (0..render_objects.len())
+            .partition(|&index| matches!(render_objects[index].bounds(), Bounds::Full));
+        let acceleration_structure = strategy.build(&render_objects, bounded_objects);
+
+        Scene { render_objects, materials, acceleration_structure, unbounded_objects, lights, light_cdf, light_slots, background_color }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::LambertianMaterial;
+    use crate::materials::DiffuseLight;
+    use crate::maths::reseed;
+    use crate::shapes::XYRect;
+    use crate::shapes::Plane;
+    use crate::shapes::Sphere;
+    use crate::textures::ConstantTexture;
+    use crate::data_structures::ScatterPayload;
+    use crate::samplers::DeltaSampler;
+    use crate::acceleration_structures::Aggregate;
+    use crate::acceleration_structures::Instance;
+    use crate::maths::Matrix4x4;
+
+    struct Mirror;
+
+    impl Material for Mirror {
+        fn emmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3) -> Color {
+            Color (0.0, 0.0, 0.0, 1.0)
+        }
+
+        fn scatter(&self, payload: &IntersectionPayload, incoming_direction: Vector3) -> Option<ScatterPayload> {
+            let pdf = DeltaSampler::new(incoming_direction.reflect(payload.normal));
+            Some(ScatterPayload { is_specular: true, attenuation: Color (0.5, 0.5, 0.5, 1.0), pdf: Box::new(pdf) })
+        }
+
+        fn transmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3, _outgoing_direction: Vector3) -> Color {
+            Color (0.0, 0.0, 0.0, 1.0)
+        }
+
+        fn scattering_pdf(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3, _outgoing_direction: Vector3) -> f64 {
+            0.0
+        }
+    }
+
+    fn floor_scene(light_material: Box<dyn Material>) -> Scene {
+        let m_white = LambertianMaterial::new(ConstantTexture::new(Color (0.5, 0.5, 0.5, 1.0)));
+        let mut render_objects: Vec<Box<dyn RenderObject>> = vec![XYRect::new(-10.0, -10.0, 10.0, 10.0, 0.0, 0)];
+        render_objects.push(XYRect::new(-3.0, -1.0, -1.0, 1.0, 2.0, 1));
+        render_objects.push(XYRect::new(1.0, -1.0, 3.0, 1.0, 2.0, 1));
+        Scene::new(render_objects, vec![m_white, light_material], Color (0.0, 0.0, 0.0, 1.0))
+    }
+
+    fn light() -> Box<dyn Material> {
+        DiffuseLight::new(ConstantTexture::new(Color (1.0, 1.0, 1.0, 1.0)), 4.0, true)
+    }
+
+    fn average_floor_color(scene: &Scene, x: f64) -> Color {
+        let settings = RenderSettings::new(1, 1, 1, 1);
+        average_floor_color_with(scene, x, &settings)
+    }
+
+    fn average_floor_color_with(scene: &Scene, x: f64, settings: &RenderSettings) -> Color {
+        let ray = Ray::new(Vector3 (x, 0.0, 1.0), Vector3 (0.0, 0.0, -1.0));
+        let samples = 2000;
+        reseed(1);
+        let mut color = Color (0.0, 0.0, 0.0, 1.0);
+        for _ in 0..samples { color = color + scene.get_color(ray, settings); }
+        color / samples as f64
+    }
+
+    #[test]
+    fn no_lights() {
+        let scene = floor_scene(LambertianMaterial::new(ConstantTexture::new(Color (1.0, 1.0, 1.0, 1.0))));
+        assert!(scene.lights.is_empty());
+
+        let color = average_floor_color(&scene, -2.0);
+        assert_eq!(color, Color (0.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn registers_emissive_objects_as_lights() {
+        let scene = floor_scene(light());
+        assert_eq!(scene.lights, vec![1, 2]);
+        assert_eq!(scene.light_slots, vec![None, Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn light_pdf_uses_the_object_hit() {
+        let scene = floor_scene(light());
+        let ray = Ray::new(Vector3 (-2.0, 0.0, 1.0), Vector3 (0.0, 0.0, 1.0));
+        let (index, _) = scene.get_intersect(&ray).unwrap();
+        assert_eq!(index, 1);
+
+        // Both lights have the same area and power, so each is chosen half the time
+        assert_eq!(scene.light_pdf(&ray, index), 0.5 * scene.render_objects[1].pdf_value(ray));
+        assert_eq!(scene.light_pdf(&ray, 0), 0.0);
+    }
+
+    #[test]
+    fn instanced_lights_are_not_sampled() {
+        let light_mesh = Aggregate::new(vec![XYRect::new(-1.0, -1.0, 1.0, 1.0, 0.0, 1)], AccelerationStrategy::default());
+        let render_objects: Vec<Box<dyn RenderObject>> = vec![
+            XYRect::new(-10.0, -10.0, 10.0, 10.0, 0.0, 0),
+            Instance::new(light_mesh, Matrix4x4::translation(Vector3 (0.0, 0.0, 2.0))),
+        ];
+        let m_white = LambertianMaterial::new(ConstantTexture::new(Color (0.5, 0.5, 0.5, 1.0)));
+        let scene = Scene::new(render_objects, vec![m_white, light()], Color (0.0, 0.0, 0.0, 1.0));
+        assert!(scene.lights.is_empty());
+
+        // The light is still reached by material sampling
+        let color = average_floor_color(&scene, 0.0);
+        assert!(color.0 > 0.1);
+    }
+
+    #[test]
+    fn one_sided_light_faces_away() {
+        // The lights face up, away from the floor
+        let scene = floor_scene(DiffuseLight::new(ConstantTexture::new(Color (1.0, 1.0, 1.0, 1.0)), 4.0, false));
+        let color = average_floor_color(&scene, -2.0);
+        assert_eq!(color, Color (0.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn samples_every_light() {
+        let scene = floor_scene(light());
+        let left = average_floor_color(&scene, -2.0);
+        let right = average_floor_color(&scene, 2.0);
+
+        assert!(left.0 > 0.1);
+        assert!(right.0 > 0.1);
+        assert!((left.0 - right.0).abs() < 0.1 * left.0);
+    }
+
+    #[test]
+    fn heuristics_agree() {
+        let scene = floor_scene(light());
+        let mut settings = RenderSettings::new(1, 1, 1, 1);
+
+        settings.mis_heuristic = MisHeuristic::Balance;
+        let balance = average_floor_color_with(&scene, -2.0, &settings);
+        settings.mis_heuristic = MisHeuristic::Power;
+        let power = average_floor_color_with(&scene, -2.0, &settings);
+
+        assert!((balance.0 - power.0).abs() < 0.1 * balance.0);
+    }
+
+    #[test]
+    fn specular_reflection_sees_light() {
+        let render_objects: Vec<Box<dyn RenderObject>> = vec![XYRect::new(-10.0, -10.0, 10.0, 10.0, 0.0, 0), XYRect::new(-1.0, -1.0, 1.0, 1.0, 2.0, 1)];
+        let scene = Scene::new(render_objects, vec![Box::new(Mirror), light()], Color (0.0, 0.0, 0.0, 1.0));
+
+        let settings = RenderSettings::new(1, 1, 1, 1);
+        let ray = Ray::new(Vector3 (-0.25, 0.0, 1.0), Vector3 (0.25, 0.0, -1.0).normalise());
+        let color = scene.get_color(ray, &settings);
+
+        // The mirror halves the light's radiance and adds no noise of its own
+        assert!((color.0 - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn strategies_agree_with_unbounded_objects() {
+        let objects = || -> Vec<Box<dyn RenderObject>> {
+            vec![Plane::new(Vector3 (0.0, 0.0, 0.0), Vector3 (0.0, 0.0, 1.0), 0), Sphere::new(Vector3 (0.0, 0.0, 1.0), 0.5, 1), Sphere::new(Vector3 (3.0, 0.0, 1.0), 0.5, 1)]
+        };
+        let materials = || -> Vec<Box<dyn Material>> { vec![Box::new(Mirror), Box::new(Mirror)] };
+        let linear = Scene::with_acceleration(objects(), materials(), Color (0.0, 0.0, 0.0, 1.0), AccelerationStrategy::None);
+        let bvh = Scene::with_acceleration(objects(), materials(), Color (0.0, 0.0, 0.0, 1.0), AccelerationStrategy::default());
+        assert_eq!(bvh.unbounded_objects, vec![0]);
+
+        for x in [-1.0, 0.0, 0.25, 3.0, 5.0] {
+            let ray = Ray::new(Vector3 (x, 0.0, 4.0), Vector3 (0.0, 0.0, -1.0));
+            let expected = linear.get_intersect(&ray).map(|(index, payload)| (index, payload.material_id, payload.distance));
+            assert_eq!(bvh.get_intersect(&ray).map(|(index, payload)| (index, payload.material_id, payload.distance)), expected);
+
+            // Stopping short of the plane leaves only the spheres to block the ray
+            let short = Ray { t_max: 3.9, ..ray };
+            let blocked = x == 0.0 || x == 0.25 || x == 3.0;
+            assert_eq!(linear.occluded(&short), blocked);
+            assert_eq!(bvh.occluded(&short), blocked);
+            assert!(bvh.occluded(&ray));
+        }
     }
 }
@@ -0,0 +1,94 @@
+use crate::data_structures::Color;
+
+// Maps linear radiance onto the displayable 0..1 range before an image is quantised. Operators work on
+// each colour channel independently and leave alpha alone
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ToneMapper {
+    // Values above one are clipped
+    #[default]
+    Clamp,
+    // x / (1 + x), which compresses highlights instead of clipping them but never reaches white
+    Reinhard,
+    // Reinhard scaled so that white_point maps to exactly one
+    ReinhardExtended { white_point: f64 },
+    // Narkowicz's fit of the ACES filmic curve
+    AcesFilmic,
+    // John Hable's filmic curve from Uncharted 2
+    Hable,
+}
+
+// Parameters of Hable's curve, and the linear value that maps to white
+const HABLE_SHOULDER_STRENGTH: f64 = 0.15;
+const HABLE_LINEAR_STRENGTH: f64 = 0.50;
+const HABLE_LINEAR_ANGLE: f64 = 0.10;
+const HABLE_TOE_STRENGTH: f64 = 0.20;
+const HABLE_TOE_NUMERATOR: f64 = 0.02;
+const HABLE_TOE_DENOMINATOR: f64 = 0.30;
+const HABLE_WHITE: f64 = 11.2;
+const HABLE_EXPOSURE_BIAS: f64 = 2.0;
+
+fn hable_partial(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (HABLE_SHOULDER_STRENGTH, HABLE_LINEAR_STRENGTH, HABLE_LINEAR_ANGLE, HABLE_TOE_STRENGTH, HABLE_TOE_NUMERATOR, HABLE_TOE_DENOMINATOR);
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+impl ToneMapper {
+    pub fn apply(&self, color: Color) -> Color {
+        let map = |x: f64| {
+            let x = x.max(0.0);
+            let mapped = match *self {
+                ToneMapper::Clamp => x,
+                ToneMapper::Reinhard => x / (1.0 + x),
+                ToneMapper::ReinhardExtended { white_point } => x * (1.0 + x / (white_point * white_point)) / (1.0 + x),
+                ToneMapper::AcesFilmic => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+                ToneMapper::Hable => hable_partial(x * HABLE_EXPOSURE_BIAS) / hable_partial(HABLE_WHITE),
+            };
+            mapped.clamp(0.0, 1.0)
+        };
+        Color (map(color.0), map(color.1), map(color.2), color.3.clamp(0.0, 1.0))
+    }
+}
+
+// Linear scale for an exposure adjustment in stops, where each stop doubles the brightness
+pub fn exposure_scale(exposure_value: f64) -> f64 {
+    2f64.powf(exposure_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [ToneMapper; 5] = [ToneMapper::Clamp, ToneMapper::Reinhard, ToneMapper::ReinhardExtended { white_point: 4.0 }, ToneMapper::AcesFilmic, ToneMapper::Hable];
+
+    fn map(operator: ToneMapper, x: f64) -> f64 {
+        operator.apply(Color (x, x, x, 1.0)).0
+    }
+
+    #[test]
+    fn reinhard_keeps_highlights_distinct() {
+        let bright = ToneMapper::Reinhard.apply(Color (4.0, 9.0, 0.0, 1.0));
+        assert_eq!(bright, Color (0.8, 0.9, 0.0, 1.0));
+        assert_eq!(ToneMapper::Clamp.apply(Color (4.0, 9.0, -1.0, 1.0)), Color (1.0, 1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn operators_are_monotonic_and_bounded() {
+        for operator in OPERATORS {
+            assert!(map(operator, 0.0).abs() < 1e-3, "{:?} should keep black", operator);
+            let mut previous = 0.0;
+            for i in 1..1000 {
+                let value = map(operator, i as f64 * 0.02);
+                assert!(value >= previous && value <= 1.0, "{:?} is not monotonic at {}", operator, i);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn white_points() {
+        assert!((map(ToneMapper::ReinhardExtended { white_point: 4.0 }, 4.0) - 1.0).abs() < 1e-12);
+        assert!((map(ToneMapper::Hable, HABLE_WHITE / HABLE_EXPOSURE_BIAS) - 1.0).abs() < 1e-12);
+        assert!(map(ToneMapper::AcesFilmic, 100.0) > 0.99);
+        assert_eq!(exposure_scale(-2.0), 0.25);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::data_structures::Image;
+use crate::data_structures::ToneMapper;
+use crate::image_formats::ImageError;
+use crate::data_structures::Color;
+use std::io::Write;
+
+static BMP_HEADER: [u8; 54] = [
+    b'B', b'M', 0, 0, 0, 0, 0, 0, 0, 0, 54, 0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 24,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+// Uncompressed 24 bit BMP, so the alpha channel is dropped
+pub fn write_bmp(image: &Image, writer: &mut impl Write, exposure: f64, tone_mapper: ToneMapper) -> Result<(), ImageError> {
+    let pixels = image.quantise(exposure, tone_mapper);
+    let mut bytes = BMP_HEADER.to_vec();
+
+    // Set the width and height values in the header
+    bytes[0x12..0x16].copy_from_slice(&(image.width as u32).to_le_bytes());
+    bytes[0x16..0x1A].copy_from_slice(&(image.height as u32).to_le_bytes());
+
+    // Rows are padded to a multiple of four bytes
+    let padding = (4 - (image.width * 3) % 4) % 4;
+    bytes.reserve_exact((image.width * 3 + padding) * image.height);
+
+    // Write the pixel data to the file in the right order
+    for row in pixels.chunks(image.width.max(1)).rev() {
+        for pixel in row {
+            bytes.push(((pixel & 0x0000ff00) >> 8) as u8);
+            bytes.push(((pixel & 0x00ff0000) >> 16) as u8);
+            bytes.push(((pixel & 0xff000000) >> 24) as u8);
+        }
+        bytes.extend(std::iter::repeat_n(0, padding));
+    }
+
+    // Set the file length in the header
+    let len = bytes.len() as u32;
+    bytes[0x02..0x06].copy_from_slice(&len.to_le_bytes());
+
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+// Reads uncompressed 24 and 32 bit BMP files, stored either bottom up or top down
+pub fn read_bmp(data: &[u8]) -> Result<Image, ImageError> {
+    let u16_at = |offset: usize| data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let u32_at = |offset: usize| data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let truncated = || ImageError::Format(String::from("truncated bmp header"));
+
+    if !data.starts_with(b"BM") { return Err(ImageError::Format(String::from("not a bmp file"))); }
+    let data_offset = u32_at(0x0A).ok_or_else(truncated)? as usize;
+    let width = u32_at(0x12).ok_or_else(truncated)? as i32;
+    let height = u32_at(0x16).ok_or_else(truncated)? as i32;
+    let bits_per_pixel = u16_at(0x1C).ok_or_else(truncated)?;
+    let compression = u32_at(0x1E).ok_or_else(truncated)?;
+
+    if compression != 0 { return Err(ImageError::Format(format!("unsupported bmp compression {}", compression))); }
+    if bits_per_pixel != 24 && bits_per_pixel != 32 { return Err(ImageError::Format(format!("unsupported bmp depth {}", bits_per_pixel))); }
+    if width <= 0 || height == 0 { return Err(ImageError::Format(String::from("invalid bmp size"))); }
+
+    // A negative height marks rows stored from the top down
+    let (width, top_down, height) = (width as usize, height < 0, height.unsigned_abs() as usize);
+    let bytes_per_pixel = bits_per_pixel as usize / 8;
+    let too_large = || ImageError::Format(format!("bmp size {}x{} is too large", width, height));
+    let stride = width.checked_mul(bytes_per_pixel).ok_or_else(too_large)?.div_ceil(4) * 4;
+    let length = stride.checked_mul(height).ok_or_else(too_large)?;
+    let pixels = data_offset.checked_add(length).and_then(|end| data.get(data_offset..end))
+        .ok_or_else(|| ImageError::Format(String::from("truncated pixel data")))?;
+
+    let mut image = Image::new(width, height);
+    for (row_index, row) in pixels.chunks(stride).enumerate() {
+        let y = if top_down { row_index } else { height - 1 - row_index };
+        for x in 0..width {
+            let pixel = &row[x * bytes_per_pixel..x * bytes_per_pixel + 3];
+            let channel = |i: usize| pixel[i] as f64 / 255.0;
+            image.set_pixel(x, y, &Color::from_srgb(channel(2), channel(1), channel(0), 1.0));
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        // An odd width exercises the row padding
+        let mut image = Image::new(3, 2);
+        image.set_pixel(0, 0, &Color (1.0, 0.0, 0.0, 1.0));
+        image.set_pixel(2, 1, &Color (0.0, 0.5, 0.0, 1.0));
+        let mut bytes = Vec::new();
+        write_bmp(&image, &mut bytes, 0.0, ToneMapper::Clamp).unwrap();
+
+        let decoded = read_bmp(&bytes).unwrap();
+        assert_eq!((decoded.width, decoded.height), (3, 2));
+        assert_eq!(decoded.get_pixel(0, 0), Color (1.0, 0.0, 0.0, 1.0));
+        assert!((decoded.get_pixel(2, 1).1 - 0.5).abs() < 0.01);
+        assert_eq!(decoded.get_pixel(1, 1), Color (1.0, 1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn empty_image() {
+        let mut bytes = Vec::new();
+        write_bmp(&Image::new(0, 3), &mut bytes, 0.0, ToneMapper::Clamp).unwrap();
+        assert_eq!(bytes.len(), BMP_HEADER.len());
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(read_bmp(b"P6 1 1 255 abc"), Err(ImageError::Format(_))));
+        assert!(matches!(read_bmp(b"BM\x00"), Err(ImageError::Format(_))));
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        // Largest width and height with the data offset near the end of the address space
+        let mut bytes = vec![0u8; 54];
+        bytes[0..2].copy_from_slice(b"BM");
+        bytes[0x0A..0x0E].copy_from_slice(&u32::MAX.to_le_bytes());
+        bytes[0x12..0x16].copy_from_slice(&i32::MAX.to_le_bytes());
+        bytes[0x16..0x1A].copy_from_slice(&i32::MIN.to_le_bytes());
+        bytes[0x1C..0x1E].copy_from_slice(&32u16.to_le_bytes());
+        assert!(matches!(read_bmp(&bytes), Err(ImageError::Format(_))));
+    }
+}
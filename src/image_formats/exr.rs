@@ -0,0 +1,297 @@
+use crate::data_structures::Color;
+use crate::data_structures::Image;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use crate::image_formats::ImageError;
+use std::io::Write;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+// Channels in the alphabetical order the format requires
+const CHANNELS: [&str; 4] = ["A", "B", "G", "R"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExrPixelType {
+    #[default]
+    Half,
+    Float,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExrCompression {
+    None,
+    // zlib over blocks of 16 scanlines
+    #[default]
+    Zip,
+}
+
+impl ExrPixelType {
+    fn id(&self) -> u32 {
+        match self {
+            ExrPixelType::Half => 1,
+            ExrPixelType::Float => 2,
+        }
+    }
+}
+
+impl ExrCompression {
+    fn id(&self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    fn lines_per_block(&self) -> usize {
+        match self {
+            ExrCompression::None => 1,
+            ExrCompression::Zip => 16,
+        }
+    }
+}
+
+// Single part scanline OpenEXR image holding linear RGBA
+pub fn write_exr(image: &Image, writer: &mut impl Write, pixel_type: ExrPixelType, compression: ExrCompression) -> Result<(), ImageError> {
+    let mut bytes = header(image, pixel_type, compression);
+
+    let lines_per_block = compression.lines_per_block();
+    let chunks: Vec<(usize, Vec<u8>)> = (0..image.height).step_by(lines_per_block).map(|y0| {
+        let y1 = (y0 + lines_per_block).min(image.height);
+        let raw = scanlines(image, y0..y1, pixel_type);
+
+        // Readers treat a chunk no smaller than the raw data as uncompressed
+        let data = match compression {
+            ExrCompression::None => raw,
+            ExrCompression::Zip => {
+                let compressed = zip(&raw);
+                if compressed.len() < raw.len() { compressed } else { raw }
+            },
+        };
+        (y0, data)
+    }).collect();
+
+    // Offset table of absolute positions, followed by the chunks
+    let mut offset = (bytes.len() + chunks.len() * 8) as u64;
+    for (_, data) in &chunks {
+        bytes.extend(offset.to_le_bytes());
+        offset += 8 + data.len() as u64;
+    }
+    for (y, data) in &chunks {
+        bytes.extend((*y as i32).to_le_bytes());
+        bytes.extend((data.len() as i32).to_le_bytes());
+        bytes.extend(data);
+    }
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+fn header(image: &Image, pixel_type: ExrPixelType, compression: ExrCompression) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(2u32.to_le_bytes());
+
+    let mut channels = Vec::new();
+    for name in CHANNELS {
+        channels.extend(name.as_bytes());
+        channels.push(0);
+        channels.extend(pixel_type.id().to_le_bytes());
+        // pLinear and three reserved bytes, then the x and y sampling rates
+        channels.extend([0, 0, 0, 0]);
+        channels.extend(1i32.to_le_bytes());
+        channels.extend(1i32.to_le_bytes());
+    }
+    channels.push(0);
+    attribute(&mut bytes, "channels", "chlist", &channels);
+    attribute(&mut bytes, "compression", "compression", &[compression.id()]);
+
+    let window: Vec<u8> = [0, 0, image.width as i32 - 1, image.height as i32 - 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+    attribute(&mut bytes, "dataWindow", "box2i", &window);
+    attribute(&mut bytes, "displayWindow", "box2i", &window);
+    attribute(&mut bytes, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut bytes, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut bytes, "screenWindowCenter", "v2f", &[0u8; 8]);
+    attribute(&mut bytes, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    bytes.push(0);
+    bytes
+}
+
+fn attribute(bytes: &mut Vec<u8>, name: &str, type_name: &str, value: &[u8]) {
+    bytes.extend(name.as_bytes());
+    bytes.push(0);
+    bytes.extend(type_name.as_bytes());
+    bytes.push(0);
+    bytes.extend((value.len() as u32).to_le_bytes());
+    bytes.extend(value);
+}
+
+// Each scanline stores every pixel of one channel before moving to the next channel
+fn scanlines(image: &Image, rows: std::ops::Range<usize>, pixel_type: ExrPixelType) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for y in rows {
+        let row: Vec<Color> = (0..image.width).map(|x| image.get_pixel(x, y)).collect();
+        for channel in CHANNELS {
+            for color in &row {
+                let value = match channel {
+                    "A" => color.3,
+                    "B" => color.2,
+                    "G" => color.1,
+                    _ => color.0,
+                } as f32;
+                match pixel_type {
+                    ExrPixelType::Half => bytes.extend(to_half(value).to_le_bytes()),
+                    ExrPixelType::Float => bytes.extend(value.to_le_bytes()),
+                }
+            }
+        }
+    }
+    bytes
+}
+
+// Bytes are split into even and odd halves and delta encoded before compression, which helps zlib with
+// the slowly varying high bytes of neighbouring values
+fn zip(raw: &[u8]) -> Vec<u8> {
+    let mut reordered: Vec<u8> = raw.iter().step_by(2).chain(raw.iter().skip(1).step_by(2)).copied().collect();
+    let mut previous = reordered.first().copied().unwrap_or(0);
+    for byte in reordered.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    compress_to_vec_zlib(&reordered, 6)
+}
+
+// Round to the nearest half precision float, ties to even
+fn to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 255 { return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 }; }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 31 { return sign | 0x7c00; }
+
+    if half_exponent <= 0 {
+        // Subnormal halves, or zero when too small
+        if half_exponent < -10 { return sign; }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - half_exponent) as u32;
+        let mut half = (mantissa >> shift) as u16;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        if remainder > halfway || (remainder == halfway && half & 1 == 1) { half += 1; }
+        return sign | half;
+    }
+
+    // A carry out of the mantissa correctly bumps the exponent
+    let mut half = sign | ((half_exponent as u16) << 10) | (mantissa >> 13) as u16;
+    let remainder = mantissa & 0x1fff;
+    if remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1) { half += 1; }
+    half
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+    fn test_image() -> Image {
+        let mut image = Image::new(5, 20);
+        for y in 0..20 {
+            for x in 0..5 {
+                image.set_pixel(x, y, &Color (x as f64 * 0.5, y as f64 * 64.0, -0.25, 1.0));
+            }
+        }
+        image
+    }
+
+    // Undo the zip stage, giving back the raw scanline bytes
+    fn unzip(data: &[u8]) -> Vec<u8> {
+        let mut reordered = decompress_to_vec_zlib(data).unwrap();
+        for i in 1..reordered.len() {
+            reordered[i] = reordered[i].wrapping_add(reordered[i - 1]).wrapping_sub(128);
+        }
+        let half = reordered.len().div_ceil(2);
+        (0..reordered.len()).map(|i| if i % 2 == 0 { reordered[i / 2] } else { reordered[half + i / 2] }).collect()
+    }
+
+    // Read back the red channel of every pixel from a written file
+    fn read_red(bytes: &[u8], pixel_type: ExrPixelType, compression: ExrCompression, width: usize, height: usize) -> Vec<f32> {
+        let header_length = header(&Image::new(width, height), pixel_type, compression).len();
+        let size = match pixel_type { ExrPixelType::Half => 2, ExrPixelType::Float => 4 };
+        let chunk_count = height.div_ceil(compression.lines_per_block());
+
+        let mut red = Vec::new();
+        for chunk in 0..chunk_count {
+            let at = header_length + chunk * 8;
+            let offset = u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap()) as usize;
+            let y = i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+            let length = i32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap()) as usize;
+            let data = &bytes[offset + 8..offset + 8 + length];
+            assert_eq!(y, chunk * compression.lines_per_block());
+
+            let lines = (height - y).min(compression.lines_per_block());
+            let raw_length = lines * width * 4 * size;
+            let raw = if length < raw_length { unzip(data) } else { data.to_vec() };
+            assert_eq!(raw.len(), raw_length);
+
+            for line in raw.chunks(width * 4 * size) {
+                // Red is the last of the four channels
+                for value in line[width * 3 * size..].chunks(size) {
+                    red.push(match pixel_type {
+                        ExrPixelType::Half => half_to_f32(u16::from_le_bytes([value[0], value[1]])),
+                        ExrPixelType::Float => f32::from_le_bytes(value.try_into().unwrap()),
+                    });
+                }
+            }
+        }
+        red
+    }
+
+    fn half_to_f32(half: u16) -> f32 {
+        let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+        let exponent = ((half >> 10) & 0x1f) as i32;
+        let mantissa = (half & 0x3ff) as f32;
+        match exponent {
+            0 => sign * mantissa * 2f32.powi(-24),
+            31 => sign * f32::INFINITY,
+            _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+        }
+    }
+
+    #[test]
+    fn half_conversion() {
+        assert_eq!(to_half(1.0), 0x3c00);
+        assert_eq!(to_half(-2.0), 0xc000);
+        assert_eq!(to_half(65504.0), 0x7bff);
+        assert_eq!(to_half(1.0e6), 0x7c00);
+        assert_eq!(to_half(5.960_464_5e-8), 0x0001);
+        assert_eq!(to_half(1.0 + 1.0 / 2048.0), 0x3c00);
+        assert_eq!(to_half(1.0 + 3.0 / 2048.0), 0x3c02);
+        assert_eq!(half_to_f32(to_half(0.333)), 0.333_007_8);
+    }
+
+    #[test]
+    fn round_trips() {
+        let image = test_image();
+        for pixel_type in [ExrPixelType::Half, ExrPixelType::Float] {
+            for compression in [ExrCompression::None, ExrCompression::Zip] {
+                let mut bytes = Vec::new();
+                write_exr(&image, &mut bytes, pixel_type, compression).unwrap();
+                assert_eq!(&bytes[..4], &MAGIC);
+
+                let red = read_red(&bytes, pixel_type, compression, 5, 20);
+                let expected: Vec<f32> = (0..20).flat_map(|_| (0..5).map(|x| x as f32 * 0.5)).collect();
+                assert_eq!(red, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn zip_is_smaller() {
+        let image = Image::new(64, 64);
+        let mut raw = Vec::new();
+        let mut zipped = Vec::new();
+        write_exr(&image, &mut raw, ExrPixelType::Float, ExrCompression::None).unwrap();
+        write_exr(&image, &mut zipped, ExrPixelType::Float, ExrCompression::Zip).unwrap();
+        assert!(zipped.len() * 10 < raw.len());
+    }
+}
@@ -0,0 +1,36 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    // The file extension does not name a format that can be read or written
+    UnsupportedFormat(String),
+    // Malformed or unsupported contents in a file being read
+    Format(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(error) => write!(f, "io error: {}", error),
+            ImageError::UnsupportedFormat(extension) => write!(f, "unsupported image format '{}'", extension),
+            ImageError::Format(message) => write!(f, "invalid image data: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageError::Io(error) => Some(error),
+            ImageError::UnsupportedFormat(_) | ImageError::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(error: io::Error) -> Self {
+        ImageError::Io(error)
+    }
+}
@@ -0,0 +1,22 @@
+mod image_error;
+mod netpbm_header;
+mod bmp;
+mod png;
+mod ppm;
+mod pfm;
+mod radiance_hdr;
+mod exr;
+
+pub use image_error::ImageError;
+pub use bmp::write_bmp;
+pub use bmp::read_bmp;
+pub use png::write_png;
+pub use ppm::write_ppm;
+pub use ppm::read_ppm;
+pub use ppm::PpmEncoding;
+pub use pfm::write_pfm;
+pub use pfm::read_pfm;
+pub use radiance_hdr::write_hdr;
+pub use exr::write_exr;
+pub use exr::ExrPixelType;
+pub use exr::ExrCompression;
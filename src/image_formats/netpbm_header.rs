@@ -0,0 +1,40 @@
+use crate::image_formats::ImageError;
+
+// Reads the whitespace separated tokens at the start of a PPM or PFM file, skipping # comments. Returns
+// them along with the offset of the data, which follows a single whitespace character
+pub(crate) fn read_header(data: &[u8], count: usize) -> Result<(Vec<String>, usize), ImageError> {
+    let mut tokens = Vec::with_capacity(count);
+    let mut position = 0;
+
+    while tokens.len() < count {
+        match data.get(position) {
+            None => return Err(ImageError::Format(String::from("truncated header"))),
+            Some(b'#') => {
+                while data.get(position).is_some_and(|&b| b != b'\n') { position += 1; }
+            },
+            Some(b) if b.is_ascii_whitespace() => position += 1,
+            Some(_) => {
+                let start = position;
+                while data.get(position).is_some_and(|b| !b.is_ascii_whitespace()) { position += 1; }
+                tokens.push(String::from_utf8_lossy(&data[start..position]).to_string());
+            },
+        }
+    }
+
+    if !data.get(position).is_some_and(|b| b.is_ascii_whitespace()) {
+        return Err(ImageError::Format(String::from("missing whitespace after header")));
+    }
+    Ok((tokens, position + 1))
+}
+
+// Number of values in a width by height image with the given values per pixel. Sizes come from the file,
+// so an empty image or one too large to address is rejected rather than overflowing
+pub(crate) fn value_count(width: usize, height: usize, values_per_pixel: usize) -> Result<usize, ImageError> {
+    if width == 0 || height == 0 { return Err(ImageError::Format(format!("invalid size {}x{}", width, height))); }
+    width.checked_mul(height).and_then(|pixels| pixels.checked_mul(values_per_pixel))
+        .ok_or_else(|| ImageError::Format(format!("image size {}x{} is too large", width, height)))
+}
+
+pub(crate) fn parse_number<T: std::str::FromStr>(token: &str, name: &str) -> Result<T, ImageError> {
+    token.parse().map_err(|_| ImageError::Format(format!("invalid {} '{}'", name, token)))
+}
@@ -0,0 +1,106 @@
+use crate::data_structures::Image;
+use crate::image_formats::ImageError;
+use crate::image_formats::netpbm_header::read_header;
+use crate::image_formats::netpbm_header::parse_number;
+use crate::image_formats::netpbm_header::value_count;
+use crate::data_structures::Color;
+use std::io::Write;
+
+// Portable float map of the linear RGB values. The negative scale marks the data as little endian, and
+// rows are stored from the bottom up
+pub fn write_pfm(image: &Image, writer: &mut impl Write) -> Result<(), ImageError> {
+    let mut bytes = format!("PF\n{} {}\n-1.0\n", image.width, image.height).into_bytes();
+    bytes.reserve_exact(image.width * image.height * 12);
+
+    for y in (0..image.height).rev() {
+        for x in 0..image.width {
+            let color = image.get_pixel(x, y);
+            for value in [color.0, color.1, color.2] {
+                bytes.extend((value as f32).to_le_bytes());
+            }
+        }
+    }
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+// Reads colour (PF) and greyscale (Pf) float maps, whose values are already linear
+pub fn read_pfm(data: &[u8]) -> Result<Image, ImageError> {
+    let (header, data_start) = read_header(data, 4)?;
+    let channels = match header[0].as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        magic => return Err(ImageError::Format(format!("not a pfm file, found '{}'", magic))),
+    };
+    let width: usize = parse_number(&header[1], "width")?;
+    let height: usize = parse_number(&header[2], "height")?;
+    let scale: f64 = parse_number(&header[3], "scale")?;
+    let little_endian = scale < 0.0;
+
+    let length = value_count(width, height, channels * 4)?;
+    let bytes = data_start.checked_add(length).and_then(|end| data.get(data_start..end))
+        .ok_or_else(|| ImageError::Format(String::from("truncated pixel data")))?;
+    let values: Vec<f64> = bytes.chunks(4).map(|b| {
+        let b = [b[0], b[1], b[2], b[3]];
+        (if little_endian { f32::from_le_bytes(b) } else { f32::from_be_bytes(b) }) as f64
+    }).collect();
+
+    let mut image = Image::new(width, height);
+    for (index, pixel) in values.chunks(channels).enumerate() {
+        let color = if channels == 3 { Color (pixel[0], pixel[1], pixel[2], 1.0) } else { Color (pixel[0], pixel[0], pixel[0], 1.0) };
+        // Rows are stored from the bottom up
+        image.set_pixel(index % width, height - 1 - index / width, &color);
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        let mut image = Image::new(2, 2);
+        image.set_pixel(0, 1, &Color (12.5, 0.0, -1.0, 1.0));
+        let mut bytes = Vec::new();
+        write_pfm(&image, &mut bytes).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes.len(), header.len() + 4 * 12);
+
+        // The bottom row comes first
+        let first = &bytes[header.len()..header.len() + 12];
+        assert_eq!(f32::from_le_bytes(first[0..4].try_into().unwrap()), 12.5);
+        assert_eq!(f32::from_le_bytes(first[8..12].try_into().unwrap()), -1.0);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut image = Image::new(3, 2);
+        image.set_pixel(2, 0, &Color (100.5, 0.25, -3.0, 1.0));
+        let mut bytes = Vec::new();
+        write_pfm(&image, &mut bytes).unwrap();
+
+        let decoded = read_pfm(&bytes).unwrap();
+        assert_eq!(decoded.get_pixel(2, 0), Color (100.5, 0.25, -3.0, 1.0));
+        assert_eq!(decoded.get_pixel(0, 1), Color (1.0, 1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn big_endian_greyscale() {
+        let mut bytes = b"Pf\n1 2\n1.0\n".to_vec();
+        bytes.extend(2.0f32.to_be_bytes());
+        bytes.extend(0.5f32.to_be_bytes());
+        let decoded = read_pfm(&bytes).unwrap();
+        assert_eq!(decoded.get_pixel(0, 0), Color (0.5, 0.5, 0.5, 1.0));
+        assert_eq!(decoded.get_pixel(0, 1), Color (2.0, 2.0, 2.0, 1.0));
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        for header in ["PF\n4294967296 4294967296\n-1\n", "Pf\n0 4\n-1\n"] {
+            assert!(matches!(read_pfm(header.as_bytes()), Err(ImageError::Format(_))));
+        }
+    }
+}
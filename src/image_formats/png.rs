@@ -0,0 +1,150 @@
+use crate::data_structures::Image;
+use crate::data_structures::ToneMapper;
+use crate::image_formats::ImageError;
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use std::io::Write;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// 8 bit RGBA PNG, keeping the alpha channel
+pub fn write_png(image: &Image, writer: &mut impl Write, exposure: f64, tone_mapper: ToneMapper) -> Result<(), ImageError> {
+    let pixels = image.quantise(exposure, tone_mapper);
+    let mut bytes = SIGNATURE.to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((image.width as u32).to_be_bytes());
+    header.extend((image.height as u32).to_be_bytes());
+    // Bit depth 8, colour type 6 (RGBA), then default compression, filtering and no interlacing
+    header.extend([8, 6, 0, 0, 0]);
+    chunk(&mut bytes, b"IHDR", &header);
+
+    let rows: Vec<Vec<u8>> = pixels.chunks(image.width.max(1)).map(|row| row.iter().flat_map(|pixel| pixel.to_be_bytes()).collect()).collect();
+    let mut filtered = Vec::with_capacity(rows.len() * (image.width * 4 + 1));
+    let empty = vec![0; image.width * 4];
+    for (y, row) in rows.iter().enumerate() {
+        let previous = if y == 0 { &empty } else { &rows[y - 1] };
+        filtered.extend(filter_row(row, previous));
+    }
+    chunk(&mut bytes, b"IDAT", &compress_to_vec_zlib(&filtered, 6));
+    chunk(&mut bytes, b"IEND", &[]);
+
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+fn chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    bytes.extend((data.len() as u32).to_be_bytes());
+    let start = bytes.len();
+    bytes.extend(kind);
+    bytes.extend(data);
+    let crc = crc32(&bytes[start..]);
+    bytes.extend(crc.to_be_bytes());
+}
+
+// Tries each of the five filters and keeps the one with the smallest sum of absolute differences, the
+// usual heuristic for what will compress best. The filter type is written before the row
+fn filter_row(row: &[u8], previous: &[u8]) -> Vec<u8> {
+    const BYTES_PER_PIXEL: usize = 4;
+    let left = |i: usize| if i >= BYTES_PER_PIXEL { row[i - BYTES_PER_PIXEL] } else { 0 };
+    let upper_left = |i: usize| if i >= BYTES_PER_PIXEL { previous[i - BYTES_PER_PIXEL] } else { 0 };
+
+    let mut best: Option<(u64, Vec<u8>)> = None;
+    for filter in 0..5u8 {
+        let mut output = Vec::with_capacity(row.len() + 1);
+        output.push(filter);
+        for i in 0..row.len() {
+            let prediction = match filter {
+                0 => 0,
+                1 => left(i),
+                2 => previous[i],
+                3 => ((left(i) as u16 + previous[i] as u16) / 2) as u8,
+                _ => paeth(left(i), previous[i], upper_left(i)),
+            };
+            output.push(row[i].wrapping_sub(prediction));
+        }
+
+        let cost = output[1..].iter().map(|&byte| (byte as i8).unsigned_abs() as u64).sum();
+        if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+            best = Some((cost, output));
+        }
+    }
+    best.map(|(_, output)| output).unwrap_or_default()
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+
+    let crc = data.iter().fold(0xffff_ffff, |crc, &byte| table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8));
+    crc ^ 0xffff_ffff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_structures::Color;
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut image = Image::new(3, 2);
+        image.set_pixel(0, 0, &Color (1.0, 0.0, 0.0, 0.0));
+        image.set_pixel(2, 1, &Color (0.0, 1.0, 0.0, 1.0));
+        // Mid grey only encodes the same way at the same exposure
+        image.set_pixel(1, 1, &Color (0.5, 0.5, 0.5, 1.0));
+        let mut bytes = Vec::new();
+        write_png(&image, &mut bytes, 0.0, ToneMapper::Clamp).unwrap();
+
+        assert_eq!(&bytes[..8], &SIGNATURE);
+        assert_eq!(&bytes[12..16], b"IHDR");
+        assert_eq!(&bytes[16..24], &[0, 0, 0, 3, 0, 0, 0, 2]);
+        assert_eq!(&bytes[bytes.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+
+        // Undo the filters and compare against the quantised pixels
+        let idat_length = u32::from_be_bytes(bytes[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&bytes[37..41], b"IDAT");
+        let filtered = decompress_to_vec_zlib(&bytes[41..41 + idat_length]).unwrap();
+        let stride = 3 * 4;
+        let mut rows: Vec<Vec<u8>> = Vec::new();
+        for line in filtered.chunks(stride + 1) {
+            let previous = rows.last().cloned().unwrap_or(vec![0; stride]);
+            let mut row: Vec<u8> = Vec::with_capacity(stride);
+            for i in 0..stride {
+                let left = if i >= 4 { row[i - 4] } else { 0 };
+                let upper_left = if i >= 4 { previous[i - 4] } else { 0 };
+                let prediction = match line[0] {
+                    0 => 0,
+                    1 => left,
+                    2 => previous[i],
+                    3 => ((left as u16 + previous[i] as u16) / 2) as u8,
+                    _ => paeth(left, previous[i], upper_left),
+                };
+                row.push(line[i + 1].wrapping_add(prediction));
+            }
+            rows.push(row);
+        }
+
+        let expected: Vec<u8> = image.quantise(0.0, ToneMapper::Clamp).iter().flat_map(|pixel| pixel.to_be_bytes()).collect();
+        assert_eq!(rows.concat(), expected);
+        // Alpha survives
+        assert_eq!(rows[0][3], 0);
+    }
+}
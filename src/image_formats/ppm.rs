@@ -0,0 +1,151 @@
+use crate::data_structures::Image;
+use crate::data_structures::ToneMapper;
+use crate::image_formats::ImageError;
+use crate::image_formats::netpbm_header::read_header;
+use crate::image_formats::netpbm_header::parse_number;
+use crate::image_formats::netpbm_header::value_count;
+use crate::data_structures::Color;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PpmEncoding {
+    // P6, raw bytes
+    #[default]
+    Binary,
+    // P3, decimal values as text
+    Ascii,
+}
+
+// 8 bit RGB portable pixmap, rows stored from the top down
+pub fn write_ppm(image: &Image, writer: &mut impl Write, exposure: f64, tone_mapper: ToneMapper, encoding: PpmEncoding) -> Result<(), ImageError> {
+    let pixels = image.quantise(exposure, tone_mapper);
+    let channels = |pixel: u32| [(pixel >> 24) as u8, (pixel >> 16) as u8, (pixel >> 8) as u8];
+
+    let bytes = match encoding {
+        PpmEncoding::Binary => {
+            let mut bytes = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+            bytes.extend(pixels.iter().flat_map(|&pixel| channels(pixel)));
+            bytes
+        },
+        PpmEncoding::Ascii => {
+            let mut text = format!("P3\n{} {}\n255\n", image.width, image.height);
+            for row in pixels.chunks(image.width.max(1)) {
+                let values: Vec<String> = row.iter().flat_map(|&pixel| channels(pixel)).map(|value| value.to_string()).collect();
+                text.push_str(&values.join(" "));
+                text.push('\n');
+            }
+            text.into_bytes()
+        },
+    };
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+// Reads binary (P6) and ASCII (P3) pixmaps with 8 or 16 bit samples, which are taken to be sRGB encoded
+pub fn read_ppm(data: &[u8]) -> Result<Image, ImageError> {
+    let (header, data_start) = read_header(data, 4)?;
+    let encoding = match header[0].as_str() {
+        "P6" => PpmEncoding::Binary,
+        "P3" => PpmEncoding::Ascii,
+        magic => return Err(ImageError::Format(format!("not a ppm file, found '{}'", magic))),
+    };
+    let width: usize = parse_number(&header[1], "width")?;
+    let height: usize = parse_number(&header[2], "height")?;
+    let max_value: u32 = parse_number(&header[3], "maximum value")?;
+    if max_value == 0 || max_value > 65535 { return Err(ImageError::Format(format!("invalid maximum value {}", max_value))); }
+
+    let sample_count = value_count(width, height, 3)?;
+    let samples: Vec<u32> = match encoding {
+        PpmEncoding::Binary => {
+            let sample_size = if max_value < 256 { 1 } else { 2 };
+            let end = sample_count.checked_mul(sample_size).and_then(|length| data_start.checked_add(length));
+            let bytes = end.and_then(|end| data.get(data_start..end))
+                .ok_or_else(|| ImageError::Format(String::from("truncated pixel data")))?;
+            if sample_size == 1 {
+                bytes.iter().map(|&b| b as u32).collect()
+            } else {
+                bytes.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as u32).collect()
+            }
+        },
+        PpmEncoding::Ascii => {
+            let text = String::from_utf8_lossy(&data[data_start..]);
+            let samples = text.split_whitespace().take(sample_count)
+                .map(|token| parse_number(token, "sample"))
+                .collect::<Result<Vec<u32>, ImageError>>()?;
+            if samples.len() < sample_count { return Err(ImageError::Format(String::from("truncated pixel data"))); }
+            samples
+        },
+    };
+
+    let mut image = Image::new(width, height);
+    for (index, rgb) in samples.chunks(3).enumerate() {
+        let channel = |i: usize| rgb[i].min(max_value) as f64 / max_value as f64;
+        image.set_pixel(index % width, index / width, &Color::from_srgb(channel(0), channel(1), channel(2), 1.0));
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> Image {
+        let mut image = Image::new(2, 1);
+        image.set_pixel(0, 0, &Color (1.0, 0.0, 0.0, 1.0));
+        image.set_pixel(1, 0, &Color (0.0, 0.0, 1.0, 1.0));
+        image
+    }
+
+    #[test]
+    fn binary() {
+        let mut bytes = Vec::new();
+        write_ppm(&image(), &mut bytes, 0.0, ToneMapper::Clamp, PpmEncoding::Binary).unwrap();
+        assert_eq!(bytes, b"P6\n2 1\n255\n\xff\x00\x00\x00\x00\xff");
+    }
+
+    #[test]
+    fn ascii() {
+        let mut bytes = Vec::new();
+        write_ppm(&image(), &mut bytes, 0.0, ToneMapper::Clamp, PpmEncoding::Ascii).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), "P3\n2 1\n255\n255 0 0 0 0 255\n");
+    }
+
+    #[test]
+    fn empty_image() {
+        for encoding in [PpmEncoding::Binary, PpmEncoding::Ascii] {
+            let mut bytes = Vec::new();
+            write_ppm(&Image::new(0, 2), &mut bytes, 0.0, ToneMapper::Clamp, encoding).unwrap();
+            assert!(bytes.ends_with(b"0 2\n255\n"));
+        }
+    }
+
+    #[test]
+    fn reads_both_encodings() {
+        for encoding in [PpmEncoding::Binary, PpmEncoding::Ascii] {
+            let mut bytes = Vec::new();
+            write_ppm(&image(), &mut bytes, 0.0, ToneMapper::Clamp, encoding).unwrap();
+            let decoded = read_ppm(&bytes).unwrap();
+            assert_eq!((decoded.width, decoded.height), (2, 1));
+            assert_eq!(decoded.get_pixel(0, 0), Color (1.0, 0.0, 0.0, 1.0));
+            assert_eq!(decoded.get_pixel(1, 0), Color (0.0, 0.0, 1.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn reads_comments_and_wide_samples() {
+        let mut bytes = b"P6 # a comment\n1 1\n# another\n65535\n".to_vec();
+        bytes.extend([0xff, 0xff, 0x00, 0x00, 0x80, 0x00]);
+        let pixel = read_ppm(&bytes).unwrap().get_pixel(0, 0);
+        assert_eq!(pixel.0, 1.0);
+        assert!((pixel.2 - 0.214).abs() < 1e-3);
+
+        assert!(matches!(read_ppm(b"P6\n2 2\n255\n\x00"), Err(ImageError::Format(_))));
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        for header in ["P6\n4294967296 4294967296\n65535\n", "P3\n18446744073709551615 2\n255\n", "P6\n0 1\n255\n"] {
+            assert!(matches!(read_ppm(header.as_bytes()), Err(ImageError::Format(_))));
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::data_structures::Color;
+use crate::data_structures::Image;
+use crate::image_formats::ImageError;
+use std::io::Write;
+
+// Radiance RGBE image. Scanlines are written flat rather than run length encoded, which every reader accepts
+pub fn write_hdr(image: &Image, writer: &mut impl Write) -> Result<(), ImageError> {
+    let mut bytes = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", image.height, image.width).into_bytes();
+    bytes.reserve_exact(image.width * image.height * 4);
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            bytes.extend(to_rgbe(image.get_pixel(x, y)));
+        }
+    }
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+// Three 8 bit mantissas sharing the exponent of the brightest channel
+fn to_rgbe(color: Color) -> [u8; 4] {
+    let (r, g, b) = (color.0.max(0.0), color.1.max(0.0), color.2.max(0.0));
+    let brightest = r.max(g).max(b);
+    if brightest < 1e-32 || !brightest.is_finite() { return [0, 0, 0, 0]; }
+
+    // brightest = mantissa * 2^exponent with the mantissa in [0.5, 1)
+    let mut exponent = brightest.log2().floor() as i32 + 1;
+    if brightest / 2f64.powi(exponent) >= 1.0 { exponent += 1; }
+    if exponent + 128 > 255 { return [255, 255, 255, 255]; }
+
+    let scale = 256.0 / 2f64.powi(exponent);
+    [(r * scale) as u8, (g * scale) as u8, (b * scale) as u8, (exponent + 128) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_rgbe(rgbe: [u8; 4]) -> Color {
+        let scale = 2f64.powi(rgbe[3] as i32 - 136);
+        Color ((rgbe[0] as f64 + 0.5) * scale, (rgbe[1] as f64 + 0.5) * scale, (rgbe[2] as f64 + 0.5) * scale, 1.0)
+    }
+
+    #[test]
+    fn rgbe_round_trip() {
+        for color in [Color (1.0, 0.5, 0.25, 1.0), Color (1000.0, 3.0, 0.0, 1.0), Color (0.001, 0.002, 0.0015, 1.0)] {
+            let decoded = from_rgbe(to_rgbe(color));
+            let brightest = color.0.max(color.1).max(color.2);
+            for (a, b) in [(color.0, decoded.0), (color.1, decoded.1), (color.2, decoded.2)] {
+                assert!((a - b).abs() <= brightest / 128.0);
+            }
+        }
+        assert_eq!(to_rgbe(Color (0.0, 0.0, 0.0, 1.0)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(Color (1.0, 0.0, 0.0, 1.0)), [128, 0, 0, 129]);
+    }
+
+    #[test]
+    fn header() {
+        let mut bytes = Vec::new();
+        write_hdr(&Image::new(3, 2), &mut bytes).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 3\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes.len(), header.len() + 6 * 4);
+    }
+}
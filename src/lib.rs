@@ -7,6 +7,13 @@ pub mod materials;
 pub mod textures;
 pub mod samplers;
 pub mod acceleration_structures;
+pub mod loaders;
+pub mod image_formats;
 mod renderer;
+mod render_settings;
 
 pub use renderer::Renderer;
+pub use render_settings::RenderSettings;
+pub use render_settings::CropWindow;
+pub use render_settings::OutputSettings;
+pub use render_settings::MisHeuristic;
@@ -0,0 +1,41 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    // Malformed data that cannot be tied to a line, such as in a binary file
+    Format(String),
+}
+
+impl LoadError {
+    pub fn parse(line: usize, message: &str) -> LoadError {
+        LoadError::Parse { line, message: message.to_string() }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "io error: {}", error),
+            LoadError::Parse { line, message } => write!(f, "parse error on line {}: {}", line, message),
+            LoadError::Format(message) => write!(f, "invalid data: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(error) => Some(error),
+            LoadError::Parse { .. } | LoadError::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
@@ -0,0 +1,98 @@
+use crate::maths::Vector3;
+use crate::data_structures::Color;
+use crate::shapes::TriangleMesh;
+use crate::traits::RenderObject;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// Indices of the attributes used by one corner of a face
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshVertex {
+    pub position: usize,
+    pub normal: Option<usize>,
+    pub uv: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshFace {
+    pub vertices: [MeshVertex; 3],
+    pub material_id: usize,
+}
+
+// Indexed triangle data shared by the mesh loaders
+#[derive(Debug, Default)]
+pub struct MeshData {
+    pub positions: Vec<Vector3>,
+    pub normals: Vec<Vector3>,
+    pub uvs: Vec<(f64, f64)>,
+    // Optional colour per position
+    pub colors: Vec<Color>,
+    pub faces: Vec<MeshFace>,
+}
+
+impl MeshData {
+    pub fn render_objects(&self) -> Vec<Box<dyn RenderObject>> {
+        TriangleMesh::triangles(&self.to_triangle_mesh())
+    }
+
+    // Merge the separately indexed attributes into shared vertices. Normals and texture coordinates are
+    // only kept when every vertex has one, colours when every position has one
+    pub fn to_triangle_mesh(&self) -> Arc<TriangleMesh> {
+        let corners = || self.faces.iter().flat_map(|face| face.vertices.iter());
+        let has_normals = corners().all(|vertex| vertex.normal.is_some());
+        let has_uvs = corners().all(|vertex| vertex.uv.is_some());
+        let has_colors = !self.colors.is_empty() && self.colors.len() == self.positions.len();
+
+        let mut vertex_indices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut colors = Vec::new();
+        let mut indices = Vec::with_capacity(self.faces.len());
+        let mut material_ids = Vec::with_capacity(self.faces.len());
+
+        for face in &self.faces {
+            let triangle = face.vertices.map(|vertex| {
+                let normal = if has_normals { vertex.normal } else { None };
+                let uv = if has_uvs { vertex.uv } else { None };
+                *vertex_indices.entry((vertex.position, normal, uv)).or_insert_with(|| {
+                    positions.push(self.positions[vertex.position]);
+                    if let Some(normal) = normal { normals.push(self.normals[normal]); }
+                    if let Some(uv) = uv { uvs.push(self.uvs[uv]); }
+                    if has_colors { colors.push(self.colors[vertex.position]); }
+                    (positions.len() - 1) as u32
+                })
+            });
+            indices.push(triangle);
+            material_ids.push(face.material_id);
+        }
+        TriangleMesh::new(positions, normals, uvs, colors, indices, material_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_vertices() {
+        let vertex = |position, normal| MeshVertex { position, normal: Some(normal), uv: None };
+        let mesh = MeshData {
+            positions: vec![Vector3 (0.0, 0.0, 0.0), Vector3 (1.0, 0.0, 0.0), Vector3 (1.0, 1.0, 0.0), Vector3 (0.0, 1.0, 0.0)],
+            normals: vec![Vector3 (0.0, 0.0, 1.0), Vector3 (0.0, 0.0, -1.0)],
+            uvs: vec![],
+            colors: vec![],
+            faces: vec![
+                MeshFace { vertices: [vertex(0, 0), vertex(1, 0), vertex(2, 0)], material_id: 0 },
+                MeshFace { vertices: [vertex(0, 0), vertex(2, 0), vertex(3, 0)], material_id: 0 },
+                MeshFace { vertices: [vertex(0, 1), vertex(2, 1), vertex(1, 1)], material_id: 1 },
+            ],
+        };
+
+        // The back face has different normals so cannot share vertices with the front
+        let triangle_mesh = mesh.to_triangle_mesh();
+        assert_eq!(triangle_mesh.triangle_count(), 3);
+        assert_eq!(mesh.render_objects().len(), 3);
+        assert_eq!(mesh.render_objects()[2].material_id(), 1);
+    }
+}
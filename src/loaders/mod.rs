@@ -0,0 +1,16 @@
+mod load_error;
+mod mesh_data;
+mod obj_loader;
+mod ply_loader;
+
+pub use load_error::LoadError;
+pub use mesh_data::MeshData;
+pub use mesh_data::MeshFace;
+pub use mesh_data::MeshVertex;
+pub use obj_loader::ObjModel;
+pub use obj_loader::MtlMaterial;
+pub use obj_loader::load_obj;
+pub use obj_loader::parse_obj;
+pub use obj_loader::parse_mtl;
+pub use ply_loader::load_ply;
+pub use ply_loader::parse_ply;
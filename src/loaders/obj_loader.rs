@@ -0,0 +1,299 @@
+use crate::loaders::LoadError;
+use crate::loaders::MeshData;
+use crate::loaders::MeshFace;
+use crate::loaders::MeshVertex;
+use crate::materials::DielectricMaterial;
+use crate::materials::DiffuseLight;
+use crate::materials::LambertianMaterial;
+use crate::data_structures::Color;
+use crate::maths::Vector3;
+use crate::textures::ConstantTexture;
+use crate::traits::Material;
+use crate::traits::RenderObject;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// Used for faces that have no material or name one missing from the material libraries
+const DEFAULT_MATERIAL: &str = "default";
+
+pub struct ObjModel {
+    pub mesh: MeshData,
+    pub materials: Vec<Box<dyn Material>>,
+    // Names of the materials, in the same order as materials
+    pub material_names: Vec<String>,
+}
+
+impl ObjModel {
+    pub fn render_objects(&self) -> Vec<Box<dyn RenderObject>> {
+        self.mesh.render_objects()
+    }
+}
+
+// Material properties read from an MTL file
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    pub diffuse: Color,
+    pub emission: Color,
+    pub refractive_index: f64,
+    pub dissolve: f64,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial { diffuse: Color (0.8, 0.8, 0.8, 1.0), emission: Color (0.0, 0.0, 0.0, 1.0), refractive_index: 1.0, dissolve: 1.0 }
+    }
+}
+
+impl MtlMaterial {
+    pub fn to_material(&self) -> Box<dyn Material> {
+        let emission = self.emission;
+        let radiance = emission.0.max(emission.1).max(emission.2);
+        if radiance > 0.0 {
+            let color = Color (emission.0 / radiance, emission.1 / radiance, emission.2 / radiance, 1.0);
+            return DiffuseLight::new(ConstantTexture::new(color), radiance, false);
+        }
+        if self.dissolve < 1.0 {
+            return DielectricMaterial::new(self.refractive_index.max(1.0), None);
+        }
+        LambertianMaterial::new(ConstantTexture::new(self.diffuse))
+    }
+}
+
+// Load an OBJ file along with any material libraries it references. Material ids of the faces start
+// at material_offset, so the model's materials can be appended to those already in a scene
+pub fn load_obj(filepath: &str, material_offset: usize) -> Result<ObjModel, LoadError> {
+    let source = fs::read_to_string(filepath)?;
+    let directory = Path::new(filepath).parent().unwrap_or(Path::new(""));
+
+    let mut libraries = HashMap::new();
+    for library in material_libraries(&source) {
+        let mtl_source = fs::read_to_string(directory.join(library))?;
+        libraries.extend(parse_mtl(&mtl_source)?);
+    }
+    parse_obj(&source, &libraries, material_offset)
+}
+
+// Tokenised like parse_obj. A line can list several libraries, and a name containing spaces runs on until
+// a token ending in .mtl
+fn material_libraries(source: &str) -> Vec<String> {
+    let mut libraries = Vec::new();
+    for line in source.lines() {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("mtllib") { continue; }
+
+        let mut name: Vec<&str> = Vec::new();
+        for token in tokens {
+            name.push(token);
+            if token.to_ascii_lowercase().ends_with(".mtl") {
+                libraries.push(name.join(" "));
+                name.clear();
+            }
+        }
+        if !name.is_empty() { libraries.push(name.join(" ")); }
+    }
+    libraries
+}
+
+pub fn parse_obj(source: &str, libraries: &HashMap<String, MtlMaterial>, material_offset: usize) -> Result<ObjModel, LoadError> {
+    let mut mesh = MeshData::default();
+    let mut material_names: Vec<String> = Vec::new();
+    let mut materials: Vec<Box<dyn Material>> = Vec::new();
+    let mut current_material = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue; };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => mesh.positions.push(parse_vector(&arguments, line_number)?),
+            "vn" => mesh.normals.push(parse_vector(&arguments, line_number)?.normalise()),
+            "vt" => {
+                let u = parse_float(arguments.first(), line_number)?;
+                let v = if arguments.len() > 1 { parse_float(arguments.get(1), line_number)? } else { 0.0 };
+                mesh.uvs.push((u, v));
+            },
+            "usemtl" => {
+                let name = arguments.first().copied().unwrap_or(DEFAULT_MATERIAL);
+                let name = if libraries.contains_key(name) { name } else { DEFAULT_MATERIAL };
+                current_material = Some(find_material(name, libraries, &mut material_names, &mut materials));
+            },
+            "f" => {
+                if arguments.len() < 3 { return Err(LoadError::parse(line_number, "face needs at least three vertices")); }
+                let vertices = arguments.iter()
+                    .map(|argument| parse_face_vertex(argument, &mesh, line_number))
+                    .collect::<Result<Vec<MeshVertex>, LoadError>>()?;
+
+                let material = match current_material {
+                    Some(material) => material,
+                    None => {
+                        let material = find_material(DEFAULT_MATERIAL, libraries, &mut material_names, &mut materials);
+                        current_material = Some(material);
+                        material
+                    }
+                };
+
+                // Split polygons into a fan of triangles around the first vertex
+                for i in 1..vertices.len() - 1 {
+                    let face = MeshFace { vertices: [vertices[0], vertices[i], vertices[i + 1]], material_id: material_offset + material };
+                    mesh.faces.push(face);
+                }
+            },
+            _ => (),
+        }
+    }
+    Ok(ObjModel { mesh, materials, material_names })
+}
+
+// Index of the named material in the model, adding it the first time it is used
+fn find_material(name: &str, libraries: &HashMap<String, MtlMaterial>, names: &mut Vec<String>, materials: &mut Vec<Box<dyn Material>>) -> usize {
+    if let Some(index) = names.iter().position(|n| n == name) { return index; }
+
+    let material = libraries.get(name).cloned().unwrap_or_default();
+    names.push(name.to_string());
+    materials.push(material.to_material());
+    names.len() - 1
+}
+
+pub fn parse_mtl(source: &str) -> Result<HashMap<String, MtlMaterial>, LoadError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue; };
+        let arguments: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() { materials.insert(name, material); }
+            let name = arguments.first().ok_or_else(|| LoadError::parse(line_number, "newmtl needs a name"))?;
+            current = Some((name.to_string(), MtlMaterial::default()));
+            continue;
+        }
+
+        let Some((_, material)) = current.as_mut() else { continue; };
+        match keyword {
+            "Kd" => material.diffuse = parse_color(&arguments, line_number)?,
+            "Ke" => material.emission = parse_color(&arguments, line_number)?,
+            "Ni" => material.refractive_index = parse_float(arguments.first(), line_number)?,
+            "d" => material.dissolve = parse_float(arguments.first(), line_number)?,
+            "Tr" => material.dissolve = 1.0 - parse_float(arguments.first(), line_number)?,
+            _ => (),
+        }
+    }
+    if let Some((name, material)) = current { materials.insert(name, material); }
+    Ok(materials)
+}
+
+fn parse_float(token: Option<&&str>, line_number: usize) -> Result<f64, LoadError> {
+    let token = token.ok_or_else(|| LoadError::parse(line_number, "missing number"))?;
+    token.parse::<f64>().map_err(|_| LoadError::parse(line_number, &format!("invalid number '{}'", token)))
+}
+
+fn parse_vector(arguments: &[&str], line_number: usize) -> Result<Vector3, LoadError> {
+    Ok(Vector3 (
+        parse_float(arguments.first(), line_number)?,
+        parse_float(arguments.get(1), line_number)?,
+        parse_float(arguments.get(2), line_number)?
+    ))
+}
+
+fn parse_color(arguments: &[&str], line_number: usize) -> Result<Color, LoadError> {
+    let v = parse_vector(arguments, line_number)?;
+    Ok(Color (v.0, v.1, v.2, 1.0))
+}
+
+// Parse one of v, v/vt, v//vn or v/vt/vn, where negative indices count back from the latest element
+fn parse_face_vertex(argument: &str, mesh: &MeshData, line_number: usize) -> Result<MeshVertex, LoadError> {
+    let mut parts = argument.split('/');
+    let position = resolve_index(parts.next(), mesh.positions.len(), line_number)?
+        .ok_or_else(|| LoadError::parse(line_number, "face vertex needs a position"))?;
+    let uv = resolve_index(parts.next(), mesh.uvs.len(), line_number)?;
+    let normal = resolve_index(parts.next(), mesh.normals.len(), line_number)?;
+    Ok(MeshVertex { position, normal, uv })
+}
+
+fn resolve_index(token: Option<&str>, count: usize, line_number: usize) -> Result<Option<usize>, LoadError> {
+    let Some(token) = token.filter(|t| !t.is_empty()) else { return Ok(None); };
+    let index = token.parse::<i64>().map_err(|_| LoadError::parse(line_number, &format!("invalid index '{}'", token)))?;
+
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(LoadError::parse(line_number, &format!("index {} out of range", index)));
+    }
+    Ok(Some(resolved as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: &str = "
+        # a unit quad and a triangle
+        mtllib scene.mtl
+        v 0 0 0
+        v 1 0 0
+        v 1 1 0
+        v 0 1 0
+        vt 0 0
+        vt 1 0
+        vt 1 1
+        vn 0 0 1
+        f 1/1/1 2/2/1 3/3/1 4//1
+        usemtl glow
+        f -4 -3 -1
+    ";
+
+    const LIBRARY: &str = "
+        newmtl glow
+        Kd 0.1 0.2 0.3
+        Ke 2 4 0
+    ";
+
+    #[test]
+    fn parse_faces() {
+        let model = parse_obj(QUAD, &parse_mtl(LIBRARY).unwrap(), 3).unwrap();
+        assert_eq!(model.mesh.positions.len(), 4);
+        assert_eq!(model.mesh.uvs.len(), 3);
+        assert_eq!(model.mesh.faces.len(), 3);
+
+        let first = model.mesh.faces[0];
+        assert_eq!(first.vertices[1], MeshVertex { position: 1, normal: Some(0), uv: Some(1) });
+        let second = model.mesh.faces[1];
+        assert_eq!(second.vertices.map(|v| v.position), [0, 2, 3]);
+        assert_eq!(second.vertices[2], MeshVertex { position: 3, normal: Some(0), uv: None });
+
+        let last = model.mesh.faces[2];
+        assert_eq!(last.vertices.map(|v| v.position), [0, 1, 3]);
+        assert_eq!(model.material_names, vec!["default", "glow"]);
+        assert_eq!(first.material_id, 3);
+        assert_eq!(last.material_id, 4);
+        assert_eq!(model.render_objects().len(), 3);
+    }
+
+    #[test]
+    fn parse_library() {
+        let library = parse_mtl(LIBRARY).unwrap();
+        let glow = &library["glow"];
+        assert_eq!(glow.diffuse, Color (0.1, 0.2, 0.3, 1.0));
+        assert!(glow.to_material().emitted_power() > 0.0);
+    }
+
+    #[test]
+    fn finds_material_libraries() {
+        let source = "mtllib\tfirst.mtl\n  mtllib my materials.mtl second.MTL\nusemtl mtllib.mtl\n";
+        assert_eq!(material_libraries(source), vec!["first.mtl", "my materials.mtl", "second.MTL"]);
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let error = parse_obj("v 0 0 0\nf 1 2 3\n", &HashMap::new(), 0).err().unwrap();
+        assert!(matches!(error, LoadError::Parse { line: 2, .. }));
+
+        let error = parse_obj("v 0 zero 0\n", &HashMap::new(), 0).err().unwrap();
+        assert!(matches!(error, LoadError::Parse { line: 1, .. }));
+    }
+}
@@ -0,0 +1,374 @@
+use crate::loaders::LoadError;
+use crate::loaders::MeshData;
+use crate::loaders::MeshFace;
+use crate::loaders::MeshVertex;
+use crate::data_structures::Color;
+use crate::maths::Vector3;
+use std::fs;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<ScalarType> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Int8),
+            "uchar" | "uint8" => Some(ScalarType::UInt8),
+            "short" | "int16" => Some(ScalarType::Int16),
+            "ushort" | "uint16" => Some(ScalarType::UInt16),
+            "int" | "int32" => Some(ScalarType::Int32),
+            "uint" | "uint32" => Some(ScalarType::UInt32),
+            "float" | "float32" => Some(ScalarType::Float32),
+            "double" | "float64" => Some(ScalarType::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    // Scale that maps integer colour channels onto 0..1
+    fn color_scale(&self) -> f64 {
+        match self {
+            ScalarType::UInt8 => 1.0 / 255.0,
+            ScalarType::UInt16 => 1.0 / 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PropertyType {
+    Scalar(ScalarType),
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Debug, Clone)]
+struct Property {
+    name: String,
+    kind: PropertyType,
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property_index(&self, names: &[&str]) -> Option<usize> {
+        self.properties.iter().position(|property| names.contains(&property.name.as_str()))
+    }
+}
+
+// Reads property values one at a time from the body of the file
+struct BodyReader<'a> {
+    format: Format,
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BodyReader<'_> {
+    fn read(&mut self, scalar_type: ScalarType) -> Result<f64, LoadError> {
+        match self.format {
+            Format::Ascii => self.read_ascii(),
+            Format::BinaryLittleEndian | Format::BinaryBigEndian => self.read_binary(scalar_type),
+        }
+    }
+
+    fn read_ascii(&mut self) -> Result<f64, LoadError> {
+        while self.position < self.data.len() && self.data[self.position].is_ascii_whitespace() { self.position += 1; }
+        let start = self.position;
+        while self.position < self.data.len() && !self.data[self.position].is_ascii_whitespace() { self.position += 1; }
+        if start == self.position { return Err(LoadError::Format(String::from("unexpected end of ply data"))); }
+
+        let token = String::from_utf8_lossy(&self.data[start..self.position]);
+        token.parse::<f64>().map_err(|_| LoadError::Format(format!("invalid ply value '{}'", token)))
+    }
+
+    fn read_binary(&mut self, scalar_type: ScalarType) -> Result<f64, LoadError> {
+        let size = scalar_type.size();
+        let Some(bytes) = self.data.get(self.position..self.position + size) else {
+            return Err(LoadError::Format(String::from("unexpected end of ply data")));
+        };
+        self.position += size;
+
+        let mut buffer = [0u8; 8];
+        buffer[..size].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian { buffer[..size].reverse(); }
+
+        Ok(match scalar_type {
+            ScalarType::Int8 => buffer[0] as i8 as f64,
+            ScalarType::UInt8 => buffer[0] as f64,
+            ScalarType::Int16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            ScalarType::UInt16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            ScalarType::Int32 => i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64,
+            ScalarType::UInt32 => u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64,
+            ScalarType::Float32 => f32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64,
+            ScalarType::Float64 => f64::from_le_bytes(buffer),
+        })
+    }
+
+    // Read every property of one element, lists are returned whole
+    fn read_element(&mut self, element: &Element) -> Result<Vec<Vec<f64>>, LoadError> {
+        let mut values = Vec::with_capacity(element.properties.len());
+        for property in &element.properties {
+            match property.kind {
+                PropertyType::Scalar(scalar_type) => values.push(vec![self.read(scalar_type)?]),
+                PropertyType::List(count_type, item_type) => {
+                    let count = whole_number(self.read(count_type)?, "list count")?;
+                    let list = (0..count).map(|_| self.read(item_type)).collect::<Result<Vec<f64>, LoadError>>()?;
+                    values.push(list);
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+// Load a PLY mesh in ascii or binary form. Every face is given material_id
+pub fn load_ply(filepath: &str, material_id: usize) -> Result<MeshData, LoadError> {
+    let data = fs::read(filepath)?;
+    parse_ply(&data, material_id)
+}
+
+pub fn parse_ply(data: &[u8], material_id: usize) -> Result<MeshData, LoadError> {
+    let (format, elements, body_start) = parse_header(data)?;
+    let mut reader = BodyReader { format, data: &data[body_start..], position: 0 };
+    let mut mesh = MeshData::default();
+
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut reader, element, &mut mesh)?,
+            "face" => read_faces(&mut reader, element, &mut mesh, material_id)?,
+            _ => for _ in 0..element.count { reader.read_element(element)?; },
+        }
+    }
+    Ok(mesh)
+}
+
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize), LoadError> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut position = 0;
+    let mut line_number = 0;
+
+    loop {
+        let Some(length) = data[position..].iter().position(|&b| b == b'\n') else {
+            return Err(LoadError::parse(line_number + 1, "missing end_header"));
+        };
+        let line = String::from_utf8_lossy(&data[position..position + length]).trim().to_string();
+        position += length + 1;
+        line_number += 1;
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["ply"] if line_number == 1 => (),
+            _ if line_number == 1 => return Err(LoadError::parse(1, "not a ply file")),
+            ["format", name, _] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(LoadError::parse(line_number, &format!("unknown format '{}'", name))),
+                });
+            },
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| LoadError::parse(line_number, "invalid element count"))?;
+                elements.push(Element { name: name.to_string(), count, properties: Vec::new() });
+            },
+            ["property", "list", count_type, item_type, name] => {
+                let (Some(count_type), Some(item_type)) = (ScalarType::parse(count_type), ScalarType::parse(item_type)) else {
+                    return Err(LoadError::parse(line_number, "unknown property type"));
+                };
+                let element = elements.last_mut().ok_or_else(|| LoadError::parse(line_number, "property before element"))?;
+                element.properties.push(Property { name: name.to_string(), kind: PropertyType::List(count_type, item_type) });
+            },
+            ["property", scalar_type, name] => {
+                let scalar_type = ScalarType::parse(scalar_type).ok_or_else(|| LoadError::parse(line_number, "unknown property type"))?;
+                let element = elements.last_mut().ok_or_else(|| LoadError::parse(line_number, "property before element"))?;
+                element.properties.push(Property { name: name.to_string(), kind: PropertyType::Scalar(scalar_type) });
+            },
+            ["end_header"] => break,
+            _ => (),
+        }
+    }
+
+    let format = format.ok_or_else(|| LoadError::parse(line_number, "missing format"))?;
+    Ok((format, elements, position))
+}
+
+// Counts and indices are read as f64, so check they are whole numbers before casting
+fn whole_number(value: f64, name: &str) -> Result<usize, LoadError> {
+    if !value.is_finite() || value < 0.0 || value.fract() != 0.0 {
+        return Err(LoadError::Format(format!("invalid {} {}", name, value)));
+    }
+    Ok(value as usize)
+}
+
+fn read_vertices(reader: &mut BodyReader, element: &Element, mesh: &mut MeshData) -> Result<(), LoadError> {
+    let find_all = |names: [&[&str]; 3]| -> Option<[usize; 3]> {
+        Some([element.property_index(names[0])?, element.property_index(names[1])?, element.property_index(names[2])?])
+    };
+    let position = find_all([&["x"], &["y"], &["z"]]).ok_or_else(|| LoadError::Format(String::from("vertex is missing a position")))?;
+    let normal = find_all([&["nx"], &["ny"], &["nz"]]);
+    let color = find_all([&["red", "r"], &["green", "g"], &["blue", "b"]]);
+    let uv = element.property_index(&["u", "s", "texture_u"]).zip(element.property_index(&["v", "t", "texture_v"]));
+
+    let color_scale = color.map_or(1.0, |[r, _, _]| match element.properties[r].kind {
+        PropertyType::Scalar(scalar_type) => scalar_type.color_scale(),
+        PropertyType::List(..) => 1.0,
+    });
+    // Scalars are read as one element lists, an empty list reads as zero
+    let get = |values: &[Vec<f64>], i: usize| values[i].first().copied().unwrap_or(0.0);
+    let vector = |values: &[Vec<f64>], [x, y, z]: [usize; 3]| Vector3 (get(values, x), get(values, y), get(values, z));
+
+    for _ in 0..element.count {
+        let values = reader.read_element(element)?;
+        mesh.positions.push(vector(&values, position));
+        if let Some(normal) = normal { mesh.normals.push(vector(&values, normal).normalise()); }
+        if let Some((u, v)) = uv { mesh.uvs.push((get(&values, u), get(&values, v))); }
+        if let Some(color) = color {
+            let c = vector(&values, color) * color_scale;
+            mesh.colors.push(Color (c.0, c.1, c.2, 1.0));
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(reader: &mut BodyReader, element: &Element, mesh: &mut MeshData, material_id: usize) -> Result<(), LoadError> {
+    let indices = element.property_index(&["vertex_indices", "vertex_index"])
+        .ok_or_else(|| LoadError::Format(String::from("face is missing vertex indices")))?;
+    let has_normals = !mesh.normals.is_empty();
+    let has_uvs = !mesh.uvs.is_empty();
+
+    for _ in 0..element.count {
+        let values = reader.read_element(element)?;
+        let vertices = values[indices].iter().map(|&index| {
+            let index = whole_number(index, "vertex index")?;
+            if index >= mesh.positions.len() { return Err(LoadError::Format(format!("vertex index {} out of range", index))); }
+            Ok(MeshVertex { position: index, normal: has_normals.then_some(index), uv: has_uvs.then_some(index) })
+        }).collect::<Result<Vec<MeshVertex>, LoadError>>()?;
+
+        // Split polygons into a fan of triangles around the first vertex
+        for i in 1..vertices.len().saturating_sub(1) {
+            mesh.faces.push(MeshFace { vertices: [vertices[0], vertices[i], vertices[i + 1]], material_id });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::TriangleMesh;
+    use crate::data_structures::Ray;
+
+    const HEADER: &str = "ply\nformat {} 1.0\ncomment test quad\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\nproperty float nx\nproperty float ny\nproperty float nz\nproperty uchar red\nproperty uchar green\nproperty uchar blue\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n";
+
+    fn header(format: &str) -> Vec<u8> {
+        HEADER.replace("{}", format).into_bytes()
+    }
+
+    fn check_quad(mesh: &MeshData) {
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.positions[2], Vector3 (1.0, 1.0, 0.0));
+        assert_eq!(mesh.normals[0], Vector3 (0.0, 0.0, 1.0));
+        assert_eq!(mesh.colors[1], Color (1.0, 0.0, 0.0, 1.0));
+        assert_eq!(mesh.faces.len(), 2);
+        assert_eq!(mesh.faces[1].vertices.map(|v| v.position), [0, 2, 3]);
+        assert_eq!(mesh.faces[1].vertices[0].normal, Some(0));
+        assert_eq!(mesh.faces[0].material_id, 7);
+    }
+
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let mut data = header(if big_endian { "binary_big_endian" } else { "binary_little_endian" });
+        let positions = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        for (i, position) in positions.iter().enumerate() {
+            for value in position.iter().chain(&[0.0, 0.0, 1.0]) {
+                data.extend(if big_endian { value.to_be_bytes() } else { value.to_le_bytes() });
+            }
+            data.extend(if i == 1 { [255, 0, 0] } else { [0, 0, 0] });
+        }
+        data.push(4);
+        for index in 0i32..4 {
+            data.extend(if big_endian { index.to_be_bytes() } else { index.to_le_bytes() });
+        }
+        data
+    }
+
+    #[test]
+    fn ascii() {
+        let mut data = header("ascii");
+        data.extend(b"0 0 0 0 0 1 0 0 0\n1 0 0 0 0 1 255 0 0\n1 1 0 0 0 1 0 0 0\n0 1 0 0 0 1 0 0 0\n4 0 1 2 3\n");
+        check_quad(&parse_ply(&data, 7).unwrap());
+    }
+
+    #[test]
+    fn binary_little_endian() {
+        check_quad(&parse_ply(&binary(false), 7).unwrap());
+    }
+
+    #[test]
+    fn binary_big_endian() {
+        check_quad(&parse_ply(&binary(true), 7).unwrap());
+    }
+
+    #[test]
+    fn colors_reach_the_mesh() {
+        let mesh = parse_ply(&binary(false), 0).unwrap().to_triangle_mesh();
+        let triangles = TriangleMesh::triangles(&mesh);
+        let payload = triangles[0].intersect(&Ray::new(Vector3 (0.9, 0.05, 1.0), Vector3 (0.0, 0.0, -1.0))).unwrap();
+        assert!(payload.vertex_color.0 > 0.8);
+        assert_eq!(payload.vertex_color.1, 0.0);
+    }
+
+    #[test]
+    fn empty_uv_lists() {
+        let data = b"ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nproperty list uchar float u\nproperty list uchar float v\nend_header\n1 2 3 0 1 0.5\n";
+        let mesh = parse_ply(data, 0).unwrap();
+        assert_eq!(mesh.uvs, vec![(0.0, 0.5)]);
+    }
+
+    #[test]
+    fn rejects_invalid_indices() {
+        for face in ["3 0 1 -1", "3 0 1 1.5", "-1 0 1 2"] {
+            let mut data = header("ascii");
+            data.extend(b"0 0 0 0 0 1 0 0 0\n1 0 0 0 0 1 255 0 0\n1 1 0 0 0 1 0 0 0\n0 1 0 0 0 1 0 0 0\n");
+            data.extend(face.as_bytes());
+            data.push(b'\n');
+            assert!(matches!(parse_ply(&data, 0), Err(LoadError::Format(_))), "{}", face);
+        }
+    }
+
+    #[test]
+    fn truncated_data() {
+        let mut data = binary(false);
+        data.truncate(data.len() - 2);
+        assert!(matches!(parse_ply(&data, 0), Err(LoadError::Format(_))));
+        assert!(matches!(parse_ply(b"obj\n", 0), Err(LoadError::Parse { line: 1, .. })));
+    }
+}
@@ -10,6 +10,7 @@ use fe_o::textures::*;
 use fe_o::traits::Material;
 use fe_o::traits::RenderObject;
 use fe_o::Renderer;
+use fe_o::RenderSettings;
 
 fn main() {
     
@@ -18,10 +19,10 @@ fn main() {
     let t_green = ConstantTexture::new(Color (0.12, 0.45, 0.15, 1.0));
     let t_light = ConstantTexture::new(Color (1.0, 1.0, 1.0, 1.0));
 
-    let m_red = LambertianMaterial::new(t_red, 0.0);
-    let m_white = LambertianMaterial::new(t_white, 0.0);
-    let m_green = LambertianMaterial::new(t_green, 0.0);
-    let m_light = LambertianMaterial::new(t_light, 15.0);
+    let m_red = LambertianMaterial::new(t_red);
+    let m_white = LambertianMaterial::new(t_white);
+    let m_green = LambertianMaterial::new(t_green);
+    let m_light = DiffuseLight::new(t_light, 15.0, true);
     let materials: Vec<Box<dyn Material>> = vec![m_red, m_white, m_green, m_light];
 
     let render_objects: Vec<Box<dyn RenderObject>> = vec![
@@ -35,7 +36,7 @@ fn main() {
     ];
 
 
-    let scene = Scene::new(render_objects, materials, vec![S::XYRect::new(213.0, 227.0, 343.0, 332.0, 540.0, 3)], Color(0.5, 0.5, 0.5, 1.0), 1);
+    let scene = Scene::new(render_objects, materials, Color(0.5, 0.5, 0.5, 1.0));
 
     let transform = Matrix4x4::create_frame_transform(
         Vector3(278.0, -800.0, 278.0),
@@ -44,7 +45,10 @@ fn main() {
     );
     let camera = PerspectiveCamera::new(transform, 40.0 * PI / 180.0, 0.0, 8.0);
 
-    let renderer = Renderer::new(scene, camera);
-    let image = renderer.render(600, 600);
-    image.save("output.bmp");
+    let settings = RenderSettings::new(600, 600, 16, 1);
+
+    let renderer = Renderer::new(scene, camera, settings);
+    let image = renderer.render();
+    let output = &renderer.settings().output;
+    image.save(&output.filepath, output.exposure, output.tone_mapper).expect("failed to save image");
 }
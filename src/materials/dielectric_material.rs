@@ -0,0 +1,102 @@
+use crate::traits::Material;
+use crate::data_structures::Color;
+use crate::maths::Vector3;
+use crate::maths::fresnel_dielectric;
+use crate::maths::random;
+use crate::data_structures::IntersectionPayload;
+use crate::data_structures::ScatterPayload;
+use crate::samplers::DeltaSampler;
+
+pub struct DielectricMaterial {
+    pub refractive_index: f64,
+    // Absorption coefficient per unit distance travelled inside the medium
+    pub absorption: Option<Color>
+}
+
+impl Material for DielectricMaterial {
+    fn emmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3) -> Color {
+        Color (0.0, 0.0, 0.0, 1.0)
+    }
+
+    fn scatter(&self, payload: &IntersectionPayload, incoming_direction: Vector3) -> Option<ScatterPayload> {
+        let direction = incoming_direction.normalise();
+        let entering = direction * payload.normal < 0.0;
+        let (normal, eta) = if entering { (payload.normal, 1.0 / self.refractive_index) } else { (payload.normal * -1.0, self.refractive_index) };
+
+        // Choose between reflection and refraction in proportion to the Fresnel reflectance
+        let reflectance = fresnel_dielectric(-(direction * normal), eta);
+        let outgoing_direction = match direction.refract(normal, eta) {
+            Some(refracted) if random::<f64>() >= reflectance => refracted,
+            _ => direction.reflect(normal),
+        };
+
+        // Hitting the surface from inside means the ray has just crossed the medium
+        let attenuation = match self.absorption {
+            Some(absorption) if !entering => Color (
+                (-absorption.0 * payload.distance).exp(),
+                (-absorption.1 * payload.distance).exp(),
+                (-absorption.2 * payload.distance).exp(),
+                1.0
+            ),
+            _ => Color (1.0, 1.0, 1.0, 1.0),
+        };
+
+        let pdf = DeltaSampler::new(outgoing_direction);
+        Some(ScatterPayload { is_specular: true, attenuation, pdf: Box::new(pdf) })
+    }
+
+    fn transmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3, _outgoing_direction: Vector3) -> Color {
+        Color (0.0, 0.0, 0.0, 1.0)
+    }
+
+    fn scattering_pdf(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3, _outgoing_direction: Vector3) -> f64 {
+        0.0
+    }
+}
+
+impl DielectricMaterial {
+    pub fn new(refractive_index: f64, absorption: Option<Color>) -> Box<DielectricMaterial> {
+        Box::new(DielectricMaterial { refractive_index, absorption })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(distance: f64) -> IntersectionPayload {
+        IntersectionPayload { position: Vector3 (0.0, 0.0, 0.0), distance, normal: Vector3 (0.0, 0.0, 1.0), material_id: 0, u: 0.0, v: 0.0, geometric_normal: Vector3 (0.0, 0.0, 1.0), error: Vector3 (0.0, 0.0, 0.0), vertex_color: Color (1.0, 1.0, 1.0, 1.0) }
+    }
+
+    #[test]
+    fn total_internal_reflection() {
+        let material = DielectricMaterial::new(1.5, None);
+        let incoming_direction = Vector3 (0.9, 0.0, 0.1).normalise();
+        for _ in 0..20 {
+            let scatter = material.scatter(&payload(1.0), incoming_direction).unwrap();
+            let outgoing_direction = scatter.pdf.generate();
+            assert!((outgoing_direction - Vector3 (0.9, 0.0, -0.1).normalise()).magnitude() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn mostly_refracts_at_normal_incidence() {
+        let material = DielectricMaterial::new(1.5, None);
+        let refracted = (0..1000)
+            .filter(|_| material.scatter(&payload(1.0), Vector3 (0.0, 0.0, -1.0)).unwrap().pdf.generate().2 < 0.0)
+            .count();
+        assert!(refracted > 900);
+    }
+
+    #[test]
+    fn beers_law_absorption() {
+        let material = DielectricMaterial::new(1.5, Some(Color (0.5, 0.0, 1.0, 1.0)));
+        let exiting = material.scatter(&payload(2.0), Vector3 (0.0, 0.0, 1.0)).unwrap();
+        assert!((exiting.attenuation.0 - (-1.0f64).exp()).abs() < 1e-12);
+        assert_eq!(exiting.attenuation.1, 1.0);
+        assert!((exiting.attenuation.2 - (-2.0f64).exp()).abs() < 1e-12);
+
+        let entering = material.scatter(&payload(2.0), Vector3 (0.0, 0.0, -1.0)).unwrap();
+        assert_eq!(entering.attenuation, Color (1.0, 1.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::traits::Material;
+use crate::traits::Texture;
+use crate::maths::Vector3;
+use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Color;
+use crate::data_structures::ScatterPayload;
+
+// Emits light equally in every direction from the front of a surface, or from both sides if two sided
+pub struct DiffuseLight {
+    pub emit: Box<dyn Texture>,
+    pub radiance: f64,
+    pub two_sided: bool
+}
+
+impl Material for DiffuseLight {
+    fn emmission(&self, payload: &IntersectionPayload, incoming_direction: Vector3) -> Color {
+        if !self.two_sided && incoming_direction * payload.normal > 0.0 { return Color (0.0, 0.0, 0.0, 1.0); }
+        self.emit.value(payload.u, payload.v, payload.position) * self.radiance
+    }
+
+    fn emitted_power(&self) -> f64 {
+        // Approximated from the middle of the texture
+        let color = self.emit.value(0.5, 0.5, Vector3 (0.0, 0.0, 0.0));
+        let sides = if self.two_sided { 2.0 } else { 1.0 };
+        (color.0 + color.1 + color.2) / 3.0 * self.radiance * sides
+    }
+
+    fn scatter(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3) -> Option<ScatterPayload> {
+        None
+    }
+
+    fn transmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3, _outgoing_direction: Vector3) -> Color {
+        Color (0.0, 0.0, 0.0, 1.0)
+    }
+
+    fn scattering_pdf(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3, _outgoing_direction: Vector3) -> f64 {
+        0.0
+    }
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Box<dyn Texture>, radiance: f64, two_sided: bool) -> Box<DiffuseLight> {
+        Box::new(DiffuseLight { emit, radiance, two_sided })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textures::ConstantTexture;
+
+    #[test]
+    fn one_sided_emission() {
+        let payload = IntersectionPayload { position: Vector3 (0.0, 0.0, 0.0), distance: 1.0, normal: Vector3 (0.0, 0.0, 1.0), material_id: 0, u: 0.0, v: 0.0, geometric_normal: Vector3 (0.0, 0.0, 1.0), error: Vector3 (0.0, 0.0, 0.0), vertex_color: Color (1.0, 1.0, 1.0, 1.0) };
+        let light = DiffuseLight::new(ConstantTexture::new(Color (1.0, 0.5, 0.25, 1.0)), 4.0, false);
+
+        assert_eq!(light.emmission(&payload, Vector3 (0.0, 0.0, -1.0)), Color (4.0, 2.0, 1.0, 1.0));
+        assert_eq!(light.emmission(&payload, Vector3 (0.0, 0.0, 1.0)), Color (0.0, 0.0, 0.0, 1.0));
+
+        let two_sided = DiffuseLight::new(ConstantTexture::new(Color (1.0, 0.5, 0.25, 1.0)), 4.0, true);
+        assert_eq!(two_sided.emmission(&payload, Vector3 (0.0, 0.0, 1.0)), Color (4.0, 2.0, 1.0, 1.0));
+    }
+}
@@ -8,8 +8,7 @@ use crate::data_structures::ScatterPayload;
 use crate::samplers::CosineSampler;
 
 pub struct LambertianMaterial {
-    pub albedo: Box<dyn Texture>,
-    pub emmissivity: f64
+    pub albedo: Box<dyn Texture>
 }
 
 impl Material for LambertianMaterial {
@@ -19,22 +18,22 @@ impl Material for LambertianMaterial {
     }
 
     fn scatter(&self, payload: &IntersectionPayload, _incoming_direction: Vector3) -> Option<ScatterPayload> {
-        let attenuation = self.albedo.value(payload.u, payload.v, payload.position);
+        let attenuation = self.albedo.value(payload.u, payload.v, payload.position) * payload.vertex_color;
         let pdf = CosineSampler::new(payload.normal);
         Some(ScatterPayload { is_specular: false, attenuation, pdf: Box::new(pdf) })
     }
 
-    fn emmission(&self, payload: &IntersectionPayload) -> Color {
-        self.albedo.value(payload.u, payload.v, payload.position) * self.emmissivity
+    fn emmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3) -> Color {
+        Color (0.0, 0.0, 0.0, 1.0)
     }
 
     fn transmission(&self, payload: &IntersectionPayload, incoming_direction: Vector3, outgoing_direction: Vector3) -> Color {
-        self.albedo.value(payload.u, payload.v, payload.position) * self.scattering_pdf(payload, incoming_direction, outgoing_direction)
+        self.albedo.value(payload.u, payload.v, payload.position) * payload.vertex_color * self.scattering_pdf(payload, incoming_direction, outgoing_direction)
     }
 }
 
 impl LambertianMaterial {
-    pub fn new(albedo: Box<dyn Texture>, emmissivity: f64) -> Box<LambertianMaterial> {
-        Box::new(LambertianMaterial { albedo, emmissivity })
+    pub fn new(albedo: Box<dyn Texture>) -> Box<LambertianMaterial> {
+        Box::new(LambertianMaterial { albedo })
     }
 }
@@ -1,5 +1,15 @@
 mod lambertian_material;
-// mod reflective_material;
+mod reflective_material;
+mod dielectric_material;
+mod rough_conductor_material;
+mod rough_dielectric_material;
+mod principled_material;
+mod diffuse_light;
 
 pub use lambertian_material::LambertianMaterial;
-// pub use reflective_material::ReflectiveMaterial;
+pub use reflective_material::ReflectiveMaterial;
+pub use dielectric_material::DielectricMaterial;
+pub use rough_conductor_material::RoughConductorMaterial;
+pub use rough_dielectric_material::RoughDielectricMaterial;
+pub use principled_material::PrincipledMaterial;
+pub use diffuse_light::DiffuseLight;
@@ -0,0 +1,209 @@
+use crate::traits::Material;
+use crate::traits::Texture;
+use crate::traits::Sampler;
+use crate::data_structures::Color;
+use crate::data_structures::IntersectionPayload;
+use crate::data_structures::ScatterPayload;
+use crate::materials::RoughDielectricMaterial;
+use crate::maths::Vector3;
+use crate::maths::GgxDistribution;
+use crate::samplers::CosineSampler;
+use crate::samplers::GgxSampler;
+use crate::samplers::WeightedSampler;
+use std::f64::consts::PI;
+
+// Roughness of the clear coat layer, which is kept fixed and glossy
+const CLEARCOAT_ROUGHNESS: f64 = 0.2;
+
+// Disney principled BSDF. Every parameter is read from a texture, scalar parameters use the red channel
+pub struct PrincipledMaterial {
+    pub base_color: Box<dyn Texture>,
+    pub metallic: Box<dyn Texture>,
+    pub roughness: Box<dyn Texture>,
+    pub specular: Box<dyn Texture>,
+    pub sheen: Box<dyn Texture>,
+    pub clearcoat: Box<dyn Texture>,
+    pub transmission: Box<dyn Texture>
+}
+
+// Parameters evaluated at a single surface point
+struct Parameters {
+    base_color: Color,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    sheen: f64,
+    clearcoat: f64,
+    transmission: f64,
+}
+
+impl Parameters {
+    fn diffuse_weight(&self) -> f64 {
+        (1.0 - self.metallic) * (1.0 - self.transmission)
+    }
+
+    fn transmission_weight(&self) -> f64 {
+        (1.0 - self.metallic) * self.transmission
+    }
+
+    fn specular_weight(&self) -> f64 {
+        1.0 - self.transmission_weight()
+    }
+
+    fn clearcoat_weight(&self) -> f64 {
+        0.25 * self.clearcoat
+    }
+
+    // Refractive index matching the normal incidence reflectance of 0.08 * specular
+    fn refractive_index(&self) -> f64 {
+        let f0 = (0.08 * self.specular).clamp(1e-4, 0.99);
+        2.0 / (1.0 - f0.sqrt()) - 1.0
+    }
+}
+
+fn schlick_weight(cosine: f64) -> f64 {
+    (1.0 - cosine).clamp(0.0, 1.0).powi(5)
+}
+
+impl Material for PrincipledMaterial {
+    fn emmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3) -> Color {
+        Color (0.0, 0.0, 0.0, 1.0)
+    }
+
+    fn scatter(&self, payload: &IntersectionPayload, incoming_direction: Vector3) -> Option<ScatterPayload> {
+        let parameters = self.parameters(payload);
+        let pdf = PrincipledMaterial::sampler(&parameters, payload, incoming_direction);
+        Some(ScatterPayload { is_specular: false, attenuation: Color (1.0, 1.0, 1.0, 1.0), pdf: Box::new(pdf) })
+    }
+
+    fn transmission(&self, payload: &IntersectionPayload, incoming_direction: Vector3, outgoing_direction: Vector3) -> Color {
+        let p = self.parameters(payload);
+        let mut color = Color (0.0, 0.0, 0.0, 1.0);
+
+        let view = (incoming_direction * -1.0).normalise();
+        let normal = if view * payload.normal >= 0.0 { payload.normal } else { payload.normal * -1.0 };
+        let cos_theta_v = normal * view;
+        let cos_theta_o = normal * outgoing_direction;
+        if cos_theta_v <= 0.0 { return color; }
+
+        if cos_theta_o > 0.0 {
+            let h = (view + outgoing_direction).normalise();
+            let cos_theta_d = outgoing_direction * h;
+
+            // Burley diffuse with retro-reflection at grazing angles, plus sheen
+            let fd90 = 0.5 + 2.0 * p.roughness * cos_theta_d * cos_theta_d;
+            let diffuse = (1.0 + (fd90 - 1.0) * schlick_weight(cos_theta_o)) * (1.0 + (fd90 - 1.0) * schlick_weight(cos_theta_v)) / PI;
+            let sheen = p.sheen * schlick_weight(cos_theta_d);
+            color = color + (p.base_color * diffuse + Color (sheen, sheen, sheen, 1.0)) * (cos_theta_o * p.diffuse_weight());
+
+            // Specular reflection, tinted by the base color as the surface becomes metallic
+            let distribution = GgxDistribution::new(p.roughness);
+            let dielectric_f0 = 0.08 * p.specular;
+            let f0 = Color (dielectric_f0, dielectric_f0, dielectric_f0, 1.0) * (1.0 - p.metallic) + p.base_color * p.metallic;
+            let fresnel = f0 + (Color (1.0, 1.0, 1.0, 1.0) + f0 * -1.0) * schlick_weight(cos_theta_d);
+            let specular = distribution.distribution(normal * h) * distribution.masking_shadowing(cos_theta_v, cos_theta_o) / (4.0 * cos_theta_v);
+            color = color + fresnel * (specular * p.specular_weight());
+
+            if p.clearcoat > 0.0 {
+                let coat = GgxDistribution::new(CLEARCOAT_ROUGHNESS);
+                let fresnel = 0.04 + 0.96 * schlick_weight(cos_theta_d);
+                let clearcoat = coat.distribution(normal * h) * coat.masking_shadowing(cos_theta_v, cos_theta_o) * fresnel / (4.0 * cos_theta_v);
+                color = color + Color (clearcoat, clearcoat, clearcoat, 1.0) * p.clearcoat_weight();
+            }
+        }
+
+        if p.transmission_weight() > 0.0 {
+            let glass = RoughDielectricMaterial { refractive_index: p.refractive_index(), distribution: GgxDistribution::new(p.roughness) };
+            let transmitted = glass.transmission(payload, incoming_direction, outgoing_direction);
+            color = color + transmitted * p.base_color * p.transmission_weight();
+        }
+        color
+    }
+
+    fn scattering_pdf(&self, payload: &IntersectionPayload, incoming_direction: Vector3, outgoing_direction: Vector3) -> f64 {
+        let parameters = self.parameters(payload);
+        PrincipledMaterial::sampler(&parameters, payload, incoming_direction).value(outgoing_direction)
+    }
+}
+
+impl PrincipledMaterial {
+    pub fn new(base_color: Box<dyn Texture>, metallic: Box<dyn Texture>, roughness: Box<dyn Texture>, specular: Box<dyn Texture>, sheen: Box<dyn Texture>, clearcoat: Box<dyn Texture>, transmission: Box<dyn Texture>) -> Box<PrincipledMaterial> {
+        Box::new(PrincipledMaterial { base_color, metallic, roughness, specular, sheen, clearcoat, transmission })
+    }
+
+    fn parameters(&self, payload: &IntersectionPayload) -> Parameters {
+        let value = |texture: &dyn Texture| texture.value(payload.u, payload.v, payload.position);
+        Parameters {
+            base_color: value(self.base_color.as_ref()) * payload.vertex_color,
+            metallic: value(self.metallic.as_ref()).0.clamp(0.0, 1.0),
+            roughness: value(self.roughness.as_ref()).0.clamp(0.0, 1.0),
+            specular: value(self.specular.as_ref()).0.max(0.0),
+            sheen: value(self.sheen.as_ref()).0.max(0.0),
+            clearcoat: value(self.clearcoat.as_ref()).0.clamp(0.0, 1.0),
+            transmission: value(self.transmission.as_ref()).0.clamp(0.0, 1.0),
+        }
+    }
+
+    // Chooses between the lobes in proportion to their weights
+    fn sampler(p: &Parameters, payload: &IntersectionPayload, incoming_direction: Vector3) -> WeightedSampler {
+        let facing_normal = if incoming_direction * payload.normal <= 0.0 { payload.normal } else { payload.normal * -1.0 };
+        let distribution = GgxDistribution::new(p.roughness);
+
+        WeightedSampler::new(vec![
+            (p.diffuse_weight(), Box::new(CosineSampler::new(facing_normal)) as Box<dyn Sampler>),
+            (p.specular_weight(), Box::new(GgxSampler::new(payload.normal, incoming_direction, distribution))),
+            (p.transmission_weight(), Box::new(GgxSampler::dielectric(payload.normal, incoming_direction, distribution, p.refractive_index()))),
+            (p.clearcoat_weight(), Box::new(GgxSampler::new(payload.normal, incoming_direction, GgxDistribution::new(CLEARCOAT_ROUGHNESS)))),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textures::ConstantTexture;
+
+    fn scalar(value: f64) -> Box<dyn Texture> {
+        ConstantTexture::new(Color (value, value, value, 1.0))
+    }
+
+    fn payload() -> IntersectionPayload {
+        IntersectionPayload { position: Vector3 (0.0, 0.0, 0.0), distance: 1.0, normal: Vector3 (0.0, 0.0, 1.0), material_id: 0, u: 0.0, v: 0.0, geometric_normal: Vector3 (0.0, 0.0, 1.0), error: Vector3 (0.0, 0.0, 0.0), vertex_color: Color (1.0, 1.0, 1.0, 1.0) }
+    }
+
+    fn albedo(material: &PrincipledMaterial, incoming_direction: Vector3) -> Color {
+        let scatter = material.scatter(&payload(), incoming_direction).unwrap();
+        let samples = 20000;
+        let mut albedo = Color (0.0, 0.0, 0.0, 1.0);
+        for _ in 0..samples {
+            let direction = scatter.pdf.generate();
+            let pdf = scatter.pdf.value(direction);
+            if pdf == 0.0 { continue; }
+            albedo = albedo + material.transmission(&payload(), incoming_direction, direction) / pdf;
+        }
+        albedo / samples as f64
+    }
+
+    #[test]
+    fn plastic_conserves_energy() {
+        let material = PrincipledMaterial::new(ConstantTexture::new(Color (0.8, 0.8, 0.8, 1.0)), scalar(0.0), scalar(0.5), scalar(0.5), scalar(0.5), scalar(1.0), scalar(0.0));
+        let result = albedo(&material, Vector3 (1.0, 0.0, -1.0).normalise());
+        assert!(result.0 > 0.6 && result.0 < 1.05);
+    }
+
+    #[test]
+    fn metal_takes_base_color() {
+        let material = PrincipledMaterial::new(ConstantTexture::new(Color (0.9, 0.5, 0.1, 1.0)), scalar(1.0), scalar(0.3), scalar(0.5), scalar(0.0), scalar(0.0), scalar(0.0));
+        let result = albedo(&material, Vector3 (0.0, 0.0, -1.0));
+        assert!(result.0 > result.1 && result.1 > result.2);
+        assert!(result.0 < 1.0);
+    }
+
+    #[test]
+    fn glass_transmits() {
+        let material = PrincipledMaterial::new(ConstantTexture::new(Color (1.0, 1.0, 1.0, 1.0)), scalar(0.0), scalar(0.2), scalar(0.5), scalar(0.0), scalar(0.0), scalar(1.0));
+        let scatter = material.scatter(&payload(), Vector3 (0.0, 0.0, -1.0)).unwrap();
+        let transmitted = (0..1000).filter(|_| scatter.pdf.generate().2 < 0.0).count();
+        assert!(transmitted > 800);
+    }
+}
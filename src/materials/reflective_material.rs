@@ -1,33 +1,80 @@
 use crate::traits::Material;
+use crate::traits::Texture;
 use crate::data_structures::Color;
 use crate::maths::Vector3;
 use crate::data_structures::IntersectionPayload;
+use crate::data_structures::ScatterPayload;
+use crate::samplers::DeltaSampler;
 
 pub struct ReflectiveMaterial {
-    reflectance: f64
+    pub tint: Box<dyn Texture>,
+    pub fuzz: f64
 }
 
 impl Material for ReflectiveMaterial {
-    fn emmission(&self, _payload: &IntersectionPayload) -> Color {
+    fn emmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3) -> Color {
         Color (0.0, 0.0, 0.0, 1.0)
     }
 
-    fn transmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3, _outgoing_direction: Vector3) -> Color {
-        Color (1.0, 1.0, 1.0, 1.0)
+    fn scatter(&self, payload: &IntersectionPayload, incoming_direction: Vector3) -> Option<ScatterPayload> {
+        // Face the normal towards the incoming ray so back faces reflect too
+        let normal = if incoming_direction * payload.normal < 0.0 { payload.normal } else { payload.normal * -1.0 };
+
+        let reflected = incoming_direction.normalise().reflect(normal);
+        let outgoing_direction = (reflected + Vector3::random_unit() * self.fuzz).normalise();
+
+        // Fuzz can push the direction below the surface, in which case the light is absorbed
+        if outgoing_direction * normal <= 0.0 { return None; }
+
+        let attenuation = self.tint.value(payload.u, payload.v, payload.position);
+        let pdf = DeltaSampler::new(outgoing_direction);
+        Some(ScatterPayload { is_specular: true, attenuation, pdf: Box::new(pdf) })
     }
 
-    fn brdf(&self, _normal: Vector3, _incoming_direction: Vector3, _outgoing_direction: Vector3) -> f64 {
-        1.0
+    fn transmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3, _outgoing_direction: Vector3) -> Color {
+        Color (0.0, 0.0, 0.0, 1.0)
     }
 
-    fn generate_direction(&self, normal: Vector3, incoming_direction: Vector3) -> Vector3 {
-        (incoming_direction.reflect(normal) + Vector3::random_unit() * self.reflectance).normalise()
+    fn scattering_pdf(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3, _outgoing_direction: Vector3) -> f64 {
+        0.0
     }
+}
 
+impl ReflectiveMaterial {
+    pub fn new(tint: Box<dyn Texture>, fuzz: f64) -> Box<ReflectiveMaterial> {
+        Box::new(ReflectiveMaterial { tint, fuzz: fuzz.clamp(0.0, 1.0) })
+    }
 }
 
-impl Reflective{
-    pub fn new(reflectance: f64) -> Box<ReflectiveMaterial> {
-        Box::new(ReflectiveMaterial { reflectance })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textures::ConstantTexture;
+
+    fn payload() -> IntersectionPayload {
+        IntersectionPayload { position: Vector3 (0.0, 0.0, 0.0), distance: 1.0, normal: Vector3 (0.0, 0.0, 1.0), material_id: 0, u: 0.0, v: 0.0, geometric_normal: Vector3 (0.0, 0.0, 1.0), error: Vector3 (0.0, 0.0, 0.0), vertex_color: Color (1.0, 1.0, 1.0, 1.0) }
+    }
+
+    #[test]
+    fn mirror_reflection() {
+        let material = ReflectiveMaterial::new(ConstantTexture::new(Color (0.9, 0.8, 0.7, 1.0)), 0.0);
+        let incoming_direction = Vector3 (1.0, 0.0, -1.0).normalise();
+        let scatter = material.scatter(&payload(), incoming_direction).unwrap();
+
+        assert!(scatter.is_specular);
+        assert_eq!(scatter.attenuation, Color (0.9, 0.8, 0.7, 1.0));
+        let outgoing_direction = scatter.pdf.generate();
+        assert!((outgoing_direction - Vector3 (1.0, 0.0, 1.0).normalise()).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn fuzzed_reflection_stays_above_surface() {
+        let material = ReflectiveMaterial::new(ConstantTexture::new(Color (1.0, 1.0, 1.0, 1.0)), 1.0);
+        let incoming_direction = Vector3 (1.0, 0.0, -0.1).normalise();
+        for _ in 0..100 {
+            if let Some(scatter) = material.scatter(&payload(), incoming_direction) {
+                assert!(scatter.pdf.generate() * payload().normal > 0.0);
+            }
+        }
     }
 }
@@ -0,0 +1,109 @@
+use crate::traits::Material;
+use crate::data_structures::Color;
+use crate::maths::Vector3;
+use crate::maths::GgxDistribution;
+use crate::maths::fresnel_conductor;
+use crate::traits::Sampler;
+use crate::data_structures::IntersectionPayload;
+use crate::data_structures::ScatterPayload;
+use crate::samplers::GgxSampler;
+
+// Glossy metal using a GGX microfacet distribution, with the complex refractive index given per channel
+pub struct RoughConductorMaterial {
+    pub eta: Color,
+    pub k: Color,
+    pub distribution: GgxDistribution
+}
+
+impl Material for RoughConductorMaterial {
+    fn emmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3) -> Color {
+        Color (0.0, 0.0, 0.0, 1.0)
+    }
+
+    fn scatter(&self, payload: &IntersectionPayload, incoming_direction: Vector3) -> Option<ScatterPayload> {
+        let pdf = GgxSampler::new(payload.normal, incoming_direction, self.distribution);
+        Some(ScatterPayload { is_specular: false, attenuation: Color (1.0, 1.0, 1.0, 1.0), pdf: Box::new(pdf) })
+    }
+
+    fn transmission(&self, payload: &IntersectionPayload, incoming_direction: Vector3, outgoing_direction: Vector3) -> Color {
+        let black = Color (0.0, 0.0, 0.0, 1.0);
+        let view = (incoming_direction * -1.0).normalise();
+        let normal = if view * payload.normal >= 0.0 { payload.normal } else { payload.normal * -1.0 };
+
+        let cos_theta_v = normal * view;
+        let cos_theta_o = normal * outgoing_direction;
+        if cos_theta_v <= 0.0 || cos_theta_o <= 0.0 { return black; }
+
+        // The cosine of the outgoing direction cancels with the one in the BRDF denominator
+        let h = (view + outgoing_direction).normalise();
+        let scale = self.distribution.distribution(normal * h) * self.distribution.masking_shadowing(cos_theta_v, cos_theta_o) / (4.0 * cos_theta_v);
+        let cos_theta_h = view * h;
+        Color (
+            fresnel_conductor(cos_theta_h, self.eta.0, self.k.0) * scale,
+            fresnel_conductor(cos_theta_h, self.eta.1, self.k.1) * scale,
+            fresnel_conductor(cos_theta_h, self.eta.2, self.k.2) * scale,
+            1.0
+        )
+    }
+
+    fn scattering_pdf(&self, payload: &IntersectionPayload, incoming_direction: Vector3, outgoing_direction: Vector3) -> f64 {
+        GgxSampler::new(payload.normal, incoming_direction, self.distribution).value(outgoing_direction)
+    }
+}
+
+impl RoughConductorMaterial {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Box<RoughConductorMaterial> {
+        Box::new(RoughConductorMaterial { eta, k, distribution: GgxDistribution::new(roughness) })
+    }
+
+    pub fn gold(roughness: f64) -> Box<RoughConductorMaterial> {
+        RoughConductorMaterial::new(Color (0.143, 0.374, 1.442, 1.0), Color (3.983, 2.385, 1.603, 1.0), roughness)
+    }
+
+    pub fn copper(roughness: f64) -> Box<RoughConductorMaterial> {
+        RoughConductorMaterial::new(Color (0.200, 0.924, 1.102, 1.0), Color (3.912, 2.452, 2.142, 1.0), roughness)
+    }
+
+    pub fn aluminium(roughness: f64) -> Box<RoughConductorMaterial> {
+        RoughConductorMaterial::new(Color (1.657, 0.880, 0.521, 1.0), Color (9.224, 6.270, 4.837, 1.0), roughness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> IntersectionPayload {
+        IntersectionPayload { position: Vector3 (0.0, 0.0, 0.0), distance: 1.0, normal: Vector3 (0.0, 0.0, 1.0), material_id: 0, u: 0.0, v: 0.0, geometric_normal: Vector3 (0.0, 0.0, 1.0), error: Vector3 (0.0, 0.0, 0.0), vertex_color: Color (1.0, 1.0, 1.0, 1.0) }
+    }
+
+    #[test]
+    fn conserves_energy() {
+        let material = RoughConductorMaterial::aluminium(0.5);
+        let incoming_direction = Vector3 (1.0, 0.0, -1.0).normalise();
+        let scatter = material.scatter(&payload(), incoming_direction).unwrap();
+
+        let samples = 20000;
+        let mut albedo = Color (0.0, 0.0, 0.0, 1.0);
+        for _ in 0..samples {
+            let direction = scatter.pdf.generate();
+            let pdf = scatter.pdf.value(direction);
+            if pdf == 0.0 { continue; }
+            albedo = albedo + material.transmission(&payload(), incoming_direction, direction) / pdf;
+        }
+        albedo = albedo / samples as f64;
+
+        // Single scattering loses some energy to masking but never gains any
+        assert!(albedo.0 < 1.0 && albedo.0 > 0.7);
+        assert!(albedo.2 < 1.0 && albedo.2 > 0.7);
+    }
+
+    #[test]
+    fn gold_is_yellow() {
+        let material = RoughConductorMaterial::gold(0.2);
+        let incoming_direction = Vector3 (0.0, 0.0, -1.0);
+        let color = material.transmission(&payload(), incoming_direction, Vector3 (0.0, 0.0, 1.0));
+        assert!(color.0 > color.2);
+        assert!(color.1 > color.2);
+    }
+}
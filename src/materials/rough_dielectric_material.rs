@@ -0,0 +1,93 @@
+use crate::traits::Material;
+use crate::data_structures::Color;
+use crate::maths::Vector3;
+use crate::maths::GgxDistribution;
+use crate::maths::fresnel_dielectric;
+use crate::traits::Sampler;
+use crate::data_structures::IntersectionPayload;
+use crate::data_structures::ScatterPayload;
+use crate::samplers::GgxSampler;
+
+// Frosted glass using a GGX microfacet distribution for both reflection and refraction
+pub struct RoughDielectricMaterial {
+    pub refractive_index: f64,
+    pub distribution: GgxDistribution
+}
+
+impl Material for RoughDielectricMaterial {
+    fn emmission(&self, _payload: &IntersectionPayload, _incoming_direction: Vector3) -> Color {
+        Color (0.0, 0.0, 0.0, 1.0)
+    }
+
+    fn scatter(&self, payload: &IntersectionPayload, incoming_direction: Vector3) -> Option<ScatterPayload> {
+        let pdf = GgxSampler::dielectric(payload.normal, incoming_direction, self.distribution, self.refractive_index);
+        Some(ScatterPayload { is_specular: false, attenuation: Color (1.0, 1.0, 1.0, 1.0), pdf: Box::new(pdf) })
+    }
+
+    fn transmission(&self, payload: &IntersectionPayload, incoming_direction: Vector3, outgoing_direction: Vector3) -> Color {
+        let view = (incoming_direction * -1.0).normalise();
+        let (normal, eta) = if view * payload.normal >= 0.0 { (payload.normal, 1.0 / self.refractive_index) } else { (payload.normal * -1.0, self.refractive_index) };
+
+        let cos_theta_v = normal * view;
+        let cos_theta_o = normal * outgoing_direction;
+        if cos_theta_v <= 0.0 || cos_theta_o == 0.0 { return Color (0.0, 0.0, 0.0, 1.0); }
+        let masking = self.distribution.masking_shadowing(cos_theta_v, cos_theta_o);
+
+        // The cosine of the outgoing direction cancels with the one in the BSDF denominator
+        let value = if cos_theta_o > 0.0 {
+            let h = (view + outgoing_direction).normalise();
+            let reflectance = fresnel_dielectric(view * h, eta);
+            self.distribution.distribution(normal * h) * masking * reflectance / (4.0 * cos_theta_v)
+        } else {
+            let mut h = (eta * view + outgoing_direction).normalise();
+            if h * normal < 0.0 { h = h * -1.0; }
+            if view * h <= 0.0 || outgoing_direction * h >= 0.0 { return Color (0.0, 0.0, 0.0, 1.0); }
+
+            // Radiance is scaled by the squared ratio of refractive indices as it changes medium
+            let transmittance = 1.0 - fresnel_dielectric(view * h, eta);
+            let denominator = (outgoing_direction * h + eta * (view * h)).powi(2);
+            self.distribution.distribution(normal * h) * masking * transmittance * eta * eta
+                * (view * h).abs() * (outgoing_direction * h).abs() / (cos_theta_v * denominator)
+        };
+        Color (value, value, value, 1.0)
+    }
+
+    fn scattering_pdf(&self, payload: &IntersectionPayload, incoming_direction: Vector3, outgoing_direction: Vector3) -> f64 {
+        GgxSampler::dielectric(payload.normal, incoming_direction, self.distribution, self.refractive_index).value(outgoing_direction)
+    }
+}
+
+impl RoughDielectricMaterial {
+    pub fn new(refractive_index: f64, roughness: f64) -> Box<RoughDielectricMaterial> {
+        Box::new(RoughDielectricMaterial { refractive_index, distribution: GgxDistribution::new(roughness) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> IntersectionPayload {
+        IntersectionPayload { position: Vector3 (0.0, 0.0, 0.0), distance: 1.0, normal: Vector3 (0.0, 0.0, 1.0), material_id: 0, u: 0.0, v: 0.0, geometric_normal: Vector3 (0.0, 0.0, 1.0), error: Vector3 (0.0, 0.0, 0.0), vertex_color: Color (1.0, 1.0, 1.0, 1.0) }
+    }
+
+    #[test]
+    fn conserves_energy() {
+        // With the radiance scaling removed, a dielectric can only lose energy to masking
+        let material = RoughDielectricMaterial::new(1.5, 0.4);
+        let incoming_direction = Vector3 (0.5, 0.0, -1.0).normalise();
+        let scatter = material.scatter(&payload(), incoming_direction).unwrap();
+
+        let samples = 20000;
+        let mut albedo = 0.0;
+        for _ in 0..samples {
+            let direction = scatter.pdf.generate();
+            let pdf = scatter.pdf.value(direction);
+            if pdf == 0.0 { continue; }
+            let scale = if direction.2 < 0.0 { 1.5 * 1.5 } else { 1.0 };
+            albedo += material.transmission(&payload(), incoming_direction, direction).0 * scale / pdf;
+        }
+        albedo /= samples as f64;
+        assert!(albedo < 1.0 && albedo > 0.85);
+    }
+}
@@ -0,0 +1,5 @@
+// Bound on the relative rounding error accumulated over n floating point operations
+pub fn gamma(n: u32) -> f64 {
+    let e = n as f64 * f64::EPSILON * 0.5;
+    e / (1.0 - e)
+}
@@ -0,0 +1,55 @@
+// Fraction of unpolarised light reflected at a smooth dielectric boundary, where cos_theta_i is measured
+// on the incident side and eta is the ratio of refractive indices (incident over transmitted)
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_theta_t = eta * eta * (1.0 - cos_theta_i * cos_theta_i);
+    if sin2_theta_t >= 1.0 { return 1.0; }
+
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    let r_s = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_p = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_s * r_s + r_p * r_p) / 2.0
+}
+
+// Fraction of light reflected by a conductor with complex refractive index eta + ik relative to the
+// incident medium
+pub fn fresnel_conductor(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos2.sqrt() * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+    (r_s + r_p) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dielectric() {
+        assert!((fresnel_dielectric(1.0, 1.0 / 1.5) - 0.04).abs() < 1e-12);
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+        assert!((fresnel_dielectric(0.0, 1.0 / 1.5) - 1.0).abs() < 1e-12);
+        assert_eq!(fresnel_dielectric(0.1, 1.5), 1.0);
+    }
+
+    #[test]
+    fn conductor() {
+        // With no absorption the conductor formula reduces to the dielectric one
+        for cos_theta_i in [1.0, 0.7, 0.2] {
+            assert!((fresnel_conductor(cos_theta_i, 1.5, 0.0) - fresnel_dielectric(cos_theta_i, 1.0 / 1.5)).abs() < 1e-9);
+        }
+        // Normal incidence reflectance ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2)
+        assert!((fresnel_conductor(1.0, 0.2, 3.9) - (0.64 + 15.21) / (1.44 + 15.21)).abs() < 1e-9);
+        assert!((fresnel_conductor(0.0, 0.2, 3.9) - 1.0).abs() < 1e-9);
+    }
+}
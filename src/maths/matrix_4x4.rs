@@ -116,6 +116,54 @@ impl Matrix4x4 {
         matrix
     }
 
+    pub fn translation(offset: Vector3) -> Matrix4x4 {
+        let mut matrix = Matrix4x4::identity();
+        for i in 0..3 { matrix[(i, 3)] = offset[i]; }
+        matrix
+    }
+
+    pub fn scale(factors: Vector3) -> Matrix4x4 {
+        let mut matrix = Matrix4x4::identity();
+        for i in 0..3 { matrix[(i, i)] = factors[i]; }
+        matrix
+    }
+
+    pub fn transpose(&self) -> Matrix4x4 {
+        let mut matrix = Matrix4x4::identity();
+        for i in 0..4 {
+            for j in 0..4 { matrix[(i, j)] = self[(j, i)]; }
+        }
+        matrix
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, None if the matrix is singular
+    pub fn inverse(&self) -> Option<Matrix4x4> {
+        let mut a = *self;
+        let mut inverse = Matrix4x4::identity();
+        for column in 0..4 {
+            let pivot = (column..4).max_by(|&x, &y| a[(x, column)].abs().total_cmp(&a[(y, column)].abs()))?;
+            if a[(pivot, column)].abs() < 1e-12 { return None; }
+            for j in 0..4 {
+                a.values.swap(column * 4 + j, pivot * 4 + j);
+                inverse.values.swap(column * 4 + j, pivot * 4 + j);
+            }
+
+            let scale = 1.0 / a[(column, column)];
+            for j in 0..4 {
+                a[(column, j)] *= scale;
+                inverse[(column, j)] *= scale;
+            }
+            for row in (0..4).filter(|&row| row != column) {
+                let factor = a[(row, column)];
+                for j in 0..4 {
+                    a[(row, j)] -= factor * a[(column, j)];
+                    inverse[(row, j)] -= factor * inverse[(column, j)];
+                }
+            }
+        }
+        Some(inverse)
+    }
+
     pub fn transform<T: Transformable>(&self, t: &T, translate: bool) -> T {
         t.transform(&self, translate)
     }
@@ -157,4 +205,17 @@ mod tests {
 
         assert_eq!(result.transform(&Vector3 (1.0, 0.0, 0.0), false), i3_basis);
     }
+
+    #[test]
+    fn inverse() {
+        let matrix = Matrix4x4::translation(Vector3 (1.0, -2.0, 3.0)) * Matrix4x4::from_i_basis(Vector3 (1.0, 1.0, 0.0).normalise()) * Matrix4x4::scale(Vector3 (2.0, 3.0, 0.5));
+        let product = matrix * matrix.inverse().unwrap();
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product[(i, j)] - expected).abs() < 1e-12);
+            }
+        }
+        assert!(Matrix4x4::scale(Vector3 (1.0, 0.0, 1.0)).inverse().is_none());
+    }
 }
@@ -0,0 +1,102 @@
+use crate::maths::Vector3;
+use crate::maths::Matrix4x4;
+use crate::maths::random;
+use std::f64::consts::PI;
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals with Smith height-correlated masking.
+// Cosines are measured against the macro surface normal
+#[derive(Debug, Clone, Copy)]
+pub struct GgxDistribution {
+    pub alpha: f64,
+}
+
+impl GgxDistribution {
+    pub fn new(roughness: f64) -> GgxDistribution {
+        // Perceptual roughness is squared, and clamped so that smooth surfaces stay numerically stable
+        GgxDistribution { alpha: (roughness * roughness).max(1e-3) }
+    }
+
+    pub fn distribution(&self, cos_theta_h: f64) -> f64 {
+        if cos_theta_h <= 0.0 { return 0.0; }
+        let alpha2 = self.alpha * self.alpha;
+        let denominator = cos_theta_h * cos_theta_h * (alpha2 - 1.0) + 1.0;
+        alpha2 / (PI * denominator * denominator)
+    }
+
+    fn lambda(&self, cos_theta: f64) -> f64 {
+        let cos2 = cos_theta * cos_theta;
+        if cos2 == 0.0 { return f64::INFINITY; }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn masking(&self, cos_theta: f64) -> f64 {
+        1.0 / (1.0 + self.lambda(cos_theta))
+    }
+
+    pub fn masking_shadowing(&self, cos_theta_i: f64, cos_theta_o: f64) -> f64 {
+        1.0 / (1.0 + self.lambda(cos_theta_i) + self.lambda(cos_theta_o))
+    }
+
+    // Density of microfacet normal h among those visible from view, per unit solid angle of h
+    pub fn visible_normal_pdf(&self, normal: Vector3, view: Vector3, h: Vector3) -> f64 {
+        let cos_theta_v = normal * view;
+        if cos_theta_v <= 0.0 { return 0.0; }
+        self.masking(cos_theta_v) * (view * h).max(0.0) * self.distribution(normal * h) / cos_theta_v
+    }
+
+    // Sample a microfacet normal from the distribution of normals visible from view (Heitz 2018)
+    pub fn sample_visible_normal(&self, normal: Vector3, view: Vector3) -> Vector3 {
+        let basis = Matrix4x4::from_i_basis(normal);
+        let tangent = basis.transform(&Vector3 (0.0, 1.0, 0.0), false);
+        let bitangent = basis.transform(&Vector3 (0.0, 0.0, 1.0), false);
+
+        // Stretch the view direction so the distribution becomes a hemisphere
+        let v = Vector3 (self.alpha * (view * tangent), self.alpha * (view * bitangent), view * normal).normalise();
+        let length2 = v.0 * v.0 + v.1 * v.1;
+        let t1 = if length2 > 0.0 { Vector3 (-v.1, v.0, 0.0) / length2.sqrt() } else { Vector3 (1.0, 0.0, 0.0) };
+        let t2 = Vector3::cross(&v, &t1);
+
+        let r = random::<f64>().sqrt();
+        let phi = 2.0 * PI * random::<f64>();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + v.2);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let n = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * v;
+
+        // Unstretch back to the ellipsoid and return to world space
+        let h = Vector3 (self.alpha * n.0, self.alpha * n.1, n.2.max(0.0)).normalise();
+        h.0 * tangent + h.1 * bitangent + h.2 * normal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distribution_is_normalised() {
+        // The projected area of the microfacets must equal that of the macro surface
+        let ggx = GgxDistribution::new(0.5);
+        let steps = 20000;
+        let mut total = 0.0;
+        for i in 0..steps {
+            let theta = (i as f64 + 0.5) / steps as f64 * PI / 2.0;
+            total += ggx.distribution(theta.cos()) * theta.cos() * theta.sin() * 2.0 * PI * (PI / 2.0 / steps as f64);
+        }
+        assert!((total - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn visible_normals_face_viewer() {
+        let ggx = GgxDistribution::new(0.7);
+        let normal = Vector3 (0.0, 0.0, 1.0);
+        let view = Vector3 (0.8, 0.0, 0.6);
+        for _ in 0..100 {
+            let h = ggx.sample_visible_normal(normal, view);
+            assert!((h.magnitude() - 1.0).abs() < 1e-9);
+            assert!(h * normal >= 0.0);
+            assert!(ggx.visible_normal_pdf(normal, view, h) > 0.0);
+        }
+    }
+}
@@ -1,5 +1,15 @@
 mod vector3;
 mod matrix_4x4;
+mod rng;
+mod fresnel;
+mod microfacet;
+mod error_bounds;
 
 pub use vector3::Vector3;
 pub use matrix_4x4::Matrix4x4;
+pub use rng::random;
+pub use rng::reseed;
+pub use fresnel::fresnel_dielectric;
+pub use fresnel::fresnel_conductor;
+pub use microfacet::GgxDistribution;
+pub use error_bounds::gamma;
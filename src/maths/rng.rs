@@ -0,0 +1,33 @@
+use rand::distributions::{Distribution, Standard};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+// Draw a random value from the calling thread's generator
+pub fn random<T>() -> T where Standard: Distribution<T> {
+    RNG.with(|rng| rng.borrow_mut().gen())
+}
+
+// Reset the calling thread's generator, used by the renderer to make each pixel's
+// samples independent of which thread renders it
+pub fn reseed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reseed() {
+        super::reseed(42);
+        let a: [f64; 4] = [random(), random(), random(), random()];
+        super::reseed(42);
+        let b: [f64; 4] = [random(), random(), random(), random()];
+        assert_eq!(a, b);
+    }
+}
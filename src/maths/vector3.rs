@@ -1,7 +1,7 @@
 use std::ops::*;
 use crate::traits::Transformable;
 use crate::maths::Matrix4x4;
-use rand::random;
+use crate::maths::random;
 use std::f64::consts::PI;
 
 #[derive(Clone, Copy, Debug)]
@@ -87,11 +87,11 @@ impl Index<usize> for Vector3 {
 impl Transformable for Vector3 {
     fn transform(&self, frame: &Matrix4x4, translate: bool) -> Self {
         let w: f64 = if translate { 1.0 } else { 0.0 };
-        return Vector3 (
+        Vector3 (
             self.0 * frame[(0, 0)] + self.1 * frame[(0, 1)] + self.2 * frame[(0, 2)] + w * frame[(0, 3)],
             self.0 * frame[(1, 0)] + self.1 * frame[(1, 1)] + self.2 * frame[(1, 2)] + w * frame[(1, 3)],
             self.0 * frame[(2, 0)] + self.1 * frame[(2, 1)] + self.2 * frame[(2, 2)] + w * frame[(2, 3)]
-        );
+        )
     }
 }
 
@@ -110,6 +110,10 @@ impl Vector3 {
         component_wise(a, b, |a, b| a.max(b))
     }
 
+    pub fn abs(&self) -> Vector3 {
+        Vector3 (self.0.abs(), self.1.abs(), self.2.abs())
+    }
+
     pub fn square_magnitude(&self) -> f64 {
         self.0 * self.0 + self.1 * self.1 + self.2 * self.2
     }
@@ -126,6 +130,17 @@ impl Vector3 {
         *self - 2.0 * (normal * *self) * normal
     }
 
+    // Bend a unit direction through a surface whose normal faces against it, where eta is the ratio of
+    // refractive indices (incident over transmitted). Returns None on total internal reflection
+    pub fn refract(&self, normal: Vector3, eta: f64) -> Option<Vector3> {
+        let cos_i = -(*self * normal);
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 { return None; }
+
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some(eta * *self + (eta * cos_i - cos_t) * normal)
+    }
+
     pub fn random_unit() -> Vector3 {
         let mut v;
         while {
@@ -208,6 +223,21 @@ mod tests {
         assert!((1.0 - result.magnitude()).abs() < 1e-10)
     }
     #[test]
+    fn refract() {
+        let normal = Vector3 (0.0, 0.0, 1.0);
+        let straight = Vector3 (0.0, 0.0, -1.0).refract(normal, 1.0 / 1.5).unwrap();
+        assert!(compare_vectors(&straight, &Vector3 (0.0, 0.0, -1.0)) < 1e-12);
+
+        // Snell's law: sin_i * n_i == sin_t * n_t
+        let incoming = Vector3 (1.0, 0.0, -1.0).normalise();
+        let result = incoming.refract(normal, 1.0 / 1.5).unwrap();
+        assert!((result.magnitude() - 1.0).abs() < 1e-12);
+        assert!((incoming.0 * 1.0 - result.0 * 1.5).abs() < 1e-12);
+
+        // Leaving glass at a grazing angle reflects totally
+        assert!(Vector3 (0.9, 0.0, -0.1).normalise().refract(normal, 1.5).is_none());
+    }
+    #[test]
     fn random_cosine_direction() {
         for _ in 0..10 {
             let result = Vector3::random_cosine_direction();
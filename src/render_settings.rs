@@ -0,0 +1,122 @@
+use crate::data_structures::ToneMapper;
+
+// Pixel region to render, from (x0, y0) inclusive to (x1, y1) exclusive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropWindow {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl CropWindow {
+    pub fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> CropWindow {
+        CropWindow { x0, y0, x1, y1 }
+    }
+}
+
+// How light sampling and material sampling are weighted against each other when combined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MisHeuristic {
+    Balance,
+    Power,
+}
+
+impl MisHeuristic {
+    // Weight of a sample drawn with pdf_a when the same direction could also have come from pdf_b
+    pub fn weight(&self, pdf_a: f64, pdf_b: f64) -> f64 {
+        let (a, b) = match self {
+            MisHeuristic::Balance => (pdf_a, pdf_b),
+            MisHeuristic::Power => (pdf_a * pdf_a, pdf_b * pdf_b),
+        };
+        if a + b == 0.0 { 0.0 } else { a / (a + b) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputSettings {
+    pub filepath: String,
+    pub show_progress: bool,
+    // Exposure adjustment in stops, applied before tone mapping
+    pub exposure: f64,
+    pub tone_mapper: ToneMapper,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    pub image_width: usize,
+    pub image_height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+    pub mis_heuristic: MisHeuristic,
+    pub seed: u64,
+    // Zero uses every available core
+    pub thread_count: usize,
+    pub crop_window: Option<CropWindow>,
+    pub output: OutputSettings,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            image_width: 600,
+            image_height: 600,
+            samples_per_pixel: 16,
+            max_depth: 5,
+            mis_heuristic: MisHeuristic::Power,
+            seed: 0,
+            thread_count: 0,
+            crop_window: None,
+            output: OutputSettings { filepath: String::from("output.bmp"), show_progress: true, exposure: 0.0, tone_mapper: ToneMapper::Clamp },
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn new(image_width: usize, image_height: usize, samples_per_pixel: usize, max_depth: usize) -> RenderSettings {
+        RenderSettings { image_width, image_height, samples_per_pixel, max_depth, ..Default::default() }
+    }
+
+    pub fn thread_count(&self) -> usize {
+        if self.thread_count > 0 { return self.thread_count; }
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
+    // The crop window clamped to the image, or the whole image when none is set
+    pub fn render_window(&self) -> CropWindow {
+        match self.crop_window {
+            None => CropWindow::new(0, 0, self.image_width, self.image_height),
+            Some(window) => CropWindow::new(
+                window.x0.min(self.image_width),
+                window.y0.min(self.image_height),
+                window.x1.min(self.image_width),
+                window.y1.min(self.image_height)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_window() {
+        let mut settings = RenderSettings::new(100, 50, 4, 2);
+        assert_eq!(settings.render_window(), CropWindow::new(0, 0, 100, 50));
+
+        settings.crop_window = Some(CropWindow::new(10, 20, 200, 40));
+        assert_eq!(settings.render_window(), CropWindow::new(10, 20, 100, 40));
+    }
+
+    #[test]
+    fn mis_weights_sum_to_one() {
+        for heuristic in [MisHeuristic::Balance, MisHeuristic::Power] {
+            let sum = heuristic.weight(0.3, 1.7) + heuristic.weight(1.7, 0.3);
+            assert!((sum - 1.0).abs() < 1e-12);
+        }
+        assert_eq!(MisHeuristic::Balance.weight(1.0, 3.0), 0.25);
+        assert_eq!(MisHeuristic::Power.weight(1.0, 3.0), 0.1);
+        assert_eq!(MisHeuristic::Power.weight(0.0, 0.0), 0.0);
+    }
+}
@@ -1,39 +1,168 @@
 use crate::data_structures::Scene;
 use crate::data_structures::Image;
 use crate::traits::Camera;
-use rand::random;
+use crate::maths::random;
+use crate::maths::reseed;
 use crate::data_structures::Color;
+use crate::render_settings::CropWindow;
+use crate::RenderSettings;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+const TILE_SIZE: usize = 16;
+
+struct Tile {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
 
 pub struct Renderer<C: Camera>{
     scene: Scene,
-    camera: C
+    camera: C,
+    settings: RenderSettings
 }
 
 impl<C: Camera> Renderer<C> {
-    pub fn new(scene: Scene, camera: C) -> Renderer<C> {
-        Renderer { scene, camera }
+    pub fn new(scene: Scene, camera: C, settings: RenderSettings) -> Renderer<C> {
+        Renderer { scene, camera, settings }
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
     }
 
-    pub fn render(self, image_width: usize, image_height: usize) -> Image {
-        let samples = 16;
+    pub fn render(&self) -> Image {
+        let image_width = self.settings.image_width;
+        let image_height = self.settings.image_height;
+        let tiles = Renderer::<C>::create_tiles(self.settings.render_window());
+        let next_tile = AtomicUsize::new(0);
+        let completed_tiles = AtomicUsize::new(0);
+        let image = Mutex::new(Image::new(image_width, image_height));
+
+        thread::scope(|scope| {
+            for _ in 0..self.settings.thread_count() {
+                scope.spawn(|| {
+                    loop {
+                        let tile_index = next_tile.fetch_add(1, Ordering::Relaxed);
+                        let Some(tile) = tiles.get(tile_index) else { break; };
 
-        let mut image = Image::new(image_width, image_height);
+                        let colors = self.render_tile(tile, image_width, image_height);
+
+                        // Copy the finished tile into the shared image in one go to keep the lock short
+                        let mut image = image.lock().unwrap();
+                        let mut colors = colors.iter();
+                        for y in tile.y0..tile.y1 {
+                            for x in tile.x0..tile.x1 {
+                                image.set_pixel(x, y, colors.next().unwrap());
+                            }
+                        }
+
+                        let completed = completed_tiles.fetch_add(1, Ordering::Relaxed) + 1;
+                        if self.settings.output.show_progress {
+                            let progress = completed * 100 / tiles.len();
+                            print!("\r[{}{}] {}%", "#".repeat(progress), "-".repeat(100 - progress), progress);
+                        }
+                    }
+                });
+            }
+        });
+        if self.settings.output.show_progress { println!(); }
+        image.into_inner().unwrap()
+    }
+
+    fn render_tile(&self, tile: &Tile, image_width: usize, image_height: usize) -> Vec<Color> {
+        let samples = self.settings.samples_per_pixel;
+
+        let mut colors = Vec::with_capacity((tile.x1 - tile.x0) * (tile.y1 - tile.y0));
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                // Seed per pixel so the result does not depend on which thread renders the tile
+                reseed(Renderer::<C>::pixel_seed(self.settings.seed, y * image_width + x));
 
-        for y in 0..image.height {
-            for x in 0..image.width {
                 let mut color = Color (0.0, 0.0, 0.0, 1.0);
                 for _ in 0..samples {
                     let ray = self.camera.generate_ray(image_width, image_height, x as f64 + random::<f64>(), y as f64 + random::<f64>());
-                    let c = self.scene.get_color(ray);
+                    let c = self.scene.get_color(ray, &self.settings);
                     color = color + c;
                 }
-                image.set_pixel(x, y, &(color / samples as f64));
+                colors.push(color / samples as f64);
+            }
+        }
+        colors
+    }
+
+    fn create_tiles(window: CropWindow) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for y0 in (window.y0..window.y1).step_by(TILE_SIZE) {
+            for x0 in (window.x0..window.x1).step_by(TILE_SIZE) {
+                let x1 = (x0 + TILE_SIZE).min(window.x1);
+                let y1 = (y0 + TILE_SIZE).min(window.y1);
+                tiles.push(Tile { x0, y0, x1, y1 });
             }
-            let progress = ((y as f64 / (image.height as f64 - 1.0)) * 100.0) as usize;
-            print!("\r[{}{}] {}%", "#".repeat(progress), "-".repeat(100 - progress), progress)
+        }
+        tiles
+    }
+
+    fn pixel_seed(seed: u64, pixel_index: usize) -> u64 {
+        seed ^ (pixel_index as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cameras::PerspectiveCamera;
+    use crate::materials::LambertianMaterial;
+    use crate::materials::DiffuseLight;
+    use crate::maths::Matrix4x4;
+    use crate::maths::Vector3;
+    use crate::shapes::Sphere;
+    use crate::shapes::XYRect;
+    use crate::textures::ConstantTexture;
+
+    fn test_renderer(settings: RenderSettings) -> Renderer<PerspectiveCamera> {
+        let m_white = LambertianMaterial::new(ConstantTexture::new(Color (0.73, 0.73, 0.73, 1.0)));
+        let m_light = DiffuseLight::new(ConstantTexture::new(Color (1.0, 1.0, 1.0, 1.0)), 15.0, true);
+        let scene = Scene::new(
+            vec![XYRect::new(-2.0, -2.0, 2.0, 2.0, 0.0, 0), Sphere::new(Vector3 (0.0, 0.0, 1.0), 1.0, 0), XYRect::new(-1.0, -1.0, 1.0, 1.0, 3.0, 1)],
+            vec![m_white, m_light],
+            Color (0.1, 0.1, 0.1, 1.0)
+        );
+        let transform = Matrix4x4::create_frame_transform(Vector3 (0.0, -6.0, 1.0), Vector3 (1.0, 0.0, 0.0), Vector3 (0.0, 1.0, 0.0));
+        let camera = PerspectiveCamera::new(transform, 0.8, 0.0, 1.0);
+        Renderer::new(scene, camera, settings)
+    }
+
+    #[test]
+    fn threaded_render_matches_single_thread() {
+        let mut settings = RenderSettings::new(37, 21, 16, 2);
+        settings.seed = 7;
+        settings.output.show_progress = false;
+
+        settings.thread_count = 1;
+        let single = test_renderer(settings.clone()).render();
+        settings.thread_count = 4;
+        let threaded = test_renderer(settings).render();
+
+        for y in 0..single.height {
+            for x in 0..single.width {
+                assert_eq!(single.get_pixel(x, y), threaded.get_pixel(x, y));
+            }
         }
-        print!("\n");
-        image
+    }
+
+    #[test]
+    fn crop_window_limits_rendered_pixels() {
+        let mut settings = RenderSettings::new(32, 32, 1, 2);
+        settings.crop_window = Some(CropWindow::new(8, 8, 16, 16));
+        settings.output.show_progress = false;
+        let image = test_renderer(settings).render();
+
+        // Pixels outside the window keep the blank image value
+        assert_eq!(image.get_pixel(0, 0), Image::new(1, 1).get_pixel(0, 0));
+        assert_eq!(image.get_pixel(31, 31), Image::new(1, 1).get_pixel(0, 0));
     }
 }
@@ -0,0 +1,23 @@
+use crate::traits::Sampler;
+use crate::maths::Vector3;
+
+// Sampler for specular scattering, where all light leaves in a single direction
+pub struct DeltaSampler {
+    direction: Vector3,
+}
+
+impl Sampler for DeltaSampler {
+    fn value(&self, _direction: Vector3) -> f64 {
+        1.0
+    }
+
+    fn generate(&self) -> Vector3 {
+        self.direction
+    }
+}
+
+impl DeltaSampler {
+    pub fn new(direction: Vector3) -> DeltaSampler {
+        DeltaSampler { direction }
+    }
+}
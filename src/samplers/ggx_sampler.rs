@@ -0,0 +1,127 @@
+use crate::traits::Sampler;
+use crate::maths::Vector3;
+use crate::maths::GgxDistribution;
+use crate::maths::fresnel_dielectric;
+use crate::maths::random;
+
+// Importance samples GGX microfacet scattering using the distribution of visible normals. Conductors
+// only reflect, dielectrics choose between reflection and refraction by the Fresnel term of the sampled facet.
+// Samples that scatter to the wrong side of the macro surface fail and are returned as a zero vector
+pub struct GgxSampler {
+    normal: Vector3,
+    view: Vector3,
+    distribution: GgxDistribution,
+    // Ratio of refractive indices, view side over far side; None for conductors
+    eta: Option<f64>,
+}
+
+impl Sampler for GgxSampler {
+    fn value(&self, direction: Vector3) -> f64 {
+        let cosine = direction * self.normal;
+        if cosine > 0.0 {
+            let h = (self.view + direction).normalise();
+            let pdf = self.distribution.visible_normal_pdf(self.normal, self.view, h) / (4.0 * (direction * h).abs());
+            match self.eta {
+                None => pdf,
+                Some(eta) => pdf * fresnel_dielectric(self.view * h, eta),
+            }
+        } else {
+            let Some(eta) = self.eta else { return 0.0; };
+            if cosine == 0.0 { return 0.0; }
+
+            let mut h = (eta * self.view + direction).normalise();
+            if h * self.normal < 0.0 { h = h * -1.0; }
+            if self.view * h <= 0.0 || direction * h >= 0.0 { return 0.0; }
+
+            // Change of variables from the half vector to the refracted direction
+            let denominator = (direction * h + eta * (self.view * h)).powi(2);
+            let transmittance = 1.0 - fresnel_dielectric(self.view * h, eta);
+            self.distribution.visible_normal_pdf(self.normal, self.view, h) * transmittance * (direction * h).abs() / denominator
+        }
+    }
+
+    fn generate(&self) -> Vector3 {
+        let h = self.distribution.sample_visible_normal(self.normal, self.view);
+        let failed = Vector3 (0.0, 0.0, 0.0);
+
+        let refracting = match self.eta {
+            None => false,
+            Some(eta) => random::<f64>() >= fresnel_dielectric(self.view * h, eta),
+        };
+
+        if refracting {
+            match (self.view * -1.0).refract(h, self.eta.unwrap()) {
+                Some(refracted) if refracted * self.normal < 0.0 => refracted,
+                _ => failed,
+            }
+        } else {
+            let reflected = (self.view * -1.0).reflect(h);
+            if reflected * self.normal > 0.0 { reflected } else { failed }
+        }
+    }
+}
+
+impl GgxSampler {
+    // Sampler for a rough conductor, seen along incoming_direction
+    pub fn new(normal: Vector3, incoming_direction: Vector3, distribution: GgxDistribution) -> GgxSampler {
+        let view = (incoming_direction * -1.0).normalise();
+        let normal = if view * normal >= 0.0 { normal } else { normal * -1.0 };
+        GgxSampler { normal, view, distribution, eta: None }
+    }
+
+    // Sampler for a rough dielectric with the given refractive index on the side the normal points away from
+    pub fn dielectric(normal: Vector3, incoming_direction: Vector3, distribution: GgxDistribution, refractive_index: f64) -> GgxSampler {
+        let view = (incoming_direction * -1.0).normalise();
+        let (normal, eta) = if view * normal >= 0.0 { (normal, 1.0 / refractive_index) } else { (normal * -1.0, refractive_index) };
+        GgxSampler { normal, view, distribution, eta: Some(eta) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maths::reseed;
+    use std::f64::consts::PI;
+
+    // The pdf integrated over the sphere must match the fraction of samples that did not fail
+    fn assert_pdf_normalised(sampler: &GgxSampler) {
+        // Seeded so the estimate, which is noisy near grazing angles, is the same on every run
+        reseed(3);
+        let samples = 200000;
+        let mut total = 0.0;
+        let mut valid = 0;
+        for _ in 0..samples {
+            total += sampler.value(Vector3::random_unit()) * 4.0 * PI;
+            if sampler.generate().square_magnitude() > 0.0 { valid += 1; }
+        }
+        let integral = total / samples as f64;
+        let valid_fraction = valid as f64 / samples as f64;
+        assert!(valid_fraction > 0.8);
+        assert!((integral - valid_fraction).abs() < 0.03);
+    }
+
+    #[test]
+    fn conductor_pdf_is_normalised() {
+        let sampler = GgxSampler::new(Vector3 (0.0, 0.0, 1.0), Vector3 (1.0, 0.0, -1.0), GgxDistribution::new(0.6));
+        assert_pdf_normalised(&sampler);
+    }
+
+    #[test]
+    fn dielectric_pdf_is_normalised() {
+        let entering = GgxSampler::dielectric(Vector3 (0.0, 0.0, 1.0), Vector3 (1.0, 0.0, -1.0), GgxDistribution::new(0.6), 1.5);
+        assert_pdf_normalised(&entering);
+
+        let exiting = GgxSampler::dielectric(Vector3 (0.0, 0.0, 1.0), Vector3 (0.3, 0.0, 1.0), GgxDistribution::new(0.6), 1.5);
+        assert_pdf_normalised(&exiting);
+    }
+
+    #[test]
+    fn generated_directions_have_positive_pdf() {
+        let sampler = GgxSampler::dielectric(Vector3 (0.0, 0.0, 1.0), Vector3 (0.5, 0.0, -1.0), GgxDistribution::new(0.3), 1.5);
+        for _ in 0..100 {
+            let direction = sampler.generate();
+            if direction.square_magnitude() == 0.0 { continue; }
+            assert!(sampler.value(direction) > 0.0);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::traits::Sampler;
 use crate::maths::Vector3;
-use rand::random;
+use crate::maths::random;
 
 pub struct MixtureSampler<'a> {
     pdf_a: &'a dyn Sampler,
@@ -1,8 +1,14 @@
 mod cosine_sampler;
 mod render_object_sampler;
 mod mixture_sampler;
+mod delta_sampler;
+mod ggx_sampler;
+mod weighted_sampler;
 
 pub use cosine_sampler::CosineSampler;
 pub use render_object_sampler::RenderObjectSampler;
 pub use mixture_sampler::MixtureSampler;
+pub use delta_sampler::DeltaSampler;
+pub use ggx_sampler::GgxSampler;
+pub use weighted_sampler::WeightedSampler;
 
@@ -5,7 +5,7 @@ use crate::data_structures::Ray;
 
 pub struct RenderObjectSampler<'a> {
     origin: Vector3,
-    render_object: &'a Box<dyn RenderObject>
+    render_object: &'a dyn RenderObject
 }
 
 impl Sampler for RenderObjectSampler<'_> {
@@ -18,8 +18,8 @@ impl Sampler for RenderObjectSampler<'_> {
     }
 }
 
-impl RenderObjectSampler<'_> {
-    pub fn new(origin: Vector3, render_object: &Box<dyn RenderObject>) -> RenderObjectSampler {
+impl<'a> RenderObjectSampler<'a> {
+    pub fn new(origin: Vector3, render_object: &'a dyn RenderObject) -> RenderObjectSampler<'a> {
         RenderObjectSampler { origin, render_object }
     }
 }
@@ -0,0 +1,56 @@
+use crate::traits::Sampler;
+use crate::maths::Vector3;
+use crate::maths::random;
+
+// Mixture of several samplers, each chosen with probability proportional to its weight
+pub struct WeightedSampler {
+    samplers: Vec<(f64, Box<dyn Sampler>)>,
+}
+
+impl Sampler for WeightedSampler {
+    fn value(&self, direction: Vector3) -> f64 {
+        // A zero direction is a failed sample from one of the components
+        if direction.square_magnitude() == 0.0 { return 0.0; }
+        self.samplers.iter().map(|(weight, sampler)| weight * sampler.value(direction)).sum()
+    }
+
+    fn generate(&self) -> Vector3 {
+        let mut target = random::<f64>();
+        for (weight, sampler) in &self.samplers {
+            if target < *weight { return sampler.generate(); }
+            target -= weight;
+        }
+        self.samplers.last().unwrap().1.generate()
+    }
+}
+
+impl WeightedSampler {
+    // Components with a zero weight are dropped, the remaining weights are normalised to sum to one
+    pub fn new(samplers: Vec<(f64, Box<dyn Sampler>)>) -> WeightedSampler {
+        let total: f64 = samplers.iter().map(|(weight, _)| weight.max(0.0)).sum();
+        let samplers = samplers.into_iter()
+            .filter(|(weight, _)| *weight > 0.0)
+            .map(|(weight, sampler)| (weight / total, sampler))
+            .collect();
+        WeightedSampler { samplers }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samplers::DeltaSampler;
+
+    #[test]
+    fn chooses_by_weight() {
+        let sampler = WeightedSampler::new(vec![
+            (3.0, Box::new(DeltaSampler::new(Vector3 (1.0, 0.0, 0.0)))),
+            (1.0, Box::new(DeltaSampler::new(Vector3 (0.0, 1.0, 0.0)))),
+            (0.0, Box::new(DeltaSampler::new(Vector3 (0.0, 0.0, 1.0)))),
+        ]);
+        let samples = 10000;
+        let first = (0..samples).filter(|_| sampler.generate().0 == 1.0).count();
+        assert!((first as f64 / samples as f64 - 0.75).abs() < 0.03);
+        assert_eq!(sampler.value(Vector3 (1.0, 0.0, 0.0)), 1.0);
+    }
+}
@@ -12,23 +12,26 @@ impl Bounds {
         Bounds::BoundingBox(a, b)
     }
 
-    pub fn intersect(&self, ray: &Ray) -> bool {
+    // Entry and exit distances of the ray, clipped to its [t_min, t_max] range
+    pub fn intersect(&self, ray: &Ray) -> Option<(f64, f64)> {
         match self {
             Bounds::BoundingBox (min_point, max_point) => {
+                let mut t_min = ray.t_min;
+                let mut t_max = ray.t_max;
                 for a in 0..3 {
                     let inv_d = 1.0 / ray.direction[a];
                     let mut t0 = (min_point[a] - ray.origin[a]) * inv_d;
                     let mut t1 = (max_point[a] - ray.origin[a]) * inv_d;
-                    if inv_d < 0.0 {
-                        let temp = t0;
-                        t0 = t1;
-                        t1 = temp;
-                    } else { () }
-                    if t1 <= t0 { return false; }
+                    if inv_d < 0.0 { std::mem::swap(&mut t0, &mut t1); }
+
+                    // max and min ignore the NaN produced when the ray lies in a slab's plane
+                    t_min = t_min.max(t0);
+                    t_max = t_max.min(t1);
+                    if t_max < t_min { return None; }
                 }
-                true
+                Some((t_min, t_max))
             },
-            Bounds::Full => true,
+            Bounds::Full => Some((ray.t_min, ray.t_max)),
         }
     }
 }
@@ -40,12 +43,29 @@ mod tests {
     #[test]
     fn intersect() {
         let bounding_box = Bounds::BoundingBox (Vector3 (0.0, 0.0, 0.0), Vector3 (1.0, 1.0, 1.0));
-        let ray = Ray { origin: Vector3 (0.5, -1.0, 0.5), direction: Vector3 (0.0, 1.0, 0.0) };
+        let ray = Ray::new(Vector3 (0.5, -1.0, 0.5), Vector3 (0.0, 1.0, 0.0));
+
+        assert!(bounding_box.intersect(&ray).is_some());
 
-        assert!(bounding_box.intersect(&ray));
+        let ray2 = Ray::new(Vector3 (0.5, -1.0, 0.5), Vector3 (0.0, -1.0, 0.0));
+
+        assert!(bounding_box.intersect(&ray2).is_none());
+    }
+
+    #[test]
+    fn interval() {
+        let bounding_box = Bounds::BoundingBox (Vector3 (0.0, 0.0, 0.0), Vector3 (1.0, 1.0, 1.0));
+        let mut ray = Ray::new(Vector3 (-1.0, 0.5, 0.5), Vector3 (1.0, 0.0, 0.0));
+        assert_eq!(bounding_box.intersect(&ray), Some((1.0, 2.0)));
 
-        let ray2 = Ray { origin: Vector3 (0.5, -1.0, 0.5), direction: Vector3 (0.0, -1.0, 0.0) };
+        ray.t_min = 1.5;
+        assert_eq!(bounding_box.intersect(&ray), Some((1.5, 2.0)));
+        ray.t_min = 0.0;
+        ray.t_max = 0.5;
+        assert_eq!(bounding_box.intersect(&ray), None);
 
-        assert!(!bounding_box.intersect(&ray2));
+        // Misses on one axis can not be made up for by overlaps on the others
+        let diagonal = Ray::new(Vector3 (-1.0, 0.5, 0.5), Vector3 (1.0, 2.0, 0.0).normalise());
+        assert!(bounding_box.intersect(&diagonal).is_none());
     }
 }
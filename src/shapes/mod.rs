@@ -2,6 +2,7 @@ mod sphere;
 mod bounds;
 mod plane;
 mod triangle;
+mod triangle_mesh;
 mod xy_rect;
 mod xz_rect;
 mod yz_rect;
@@ -10,6 +11,8 @@ pub use sphere::Sphere;
 pub use bounds::Bounds;
 pub use plane::Plane;
 pub use triangle::Triangle;
+pub use triangle_mesh::TriangleMesh;
+pub use triangle_mesh::MeshTriangle;
 pub use xy_rect::XYRect;
 pub use xz_rect::XZRect;
 pub use yz_rect::YZRect;
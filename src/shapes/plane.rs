@@ -2,7 +2,9 @@ use crate::maths::Vector3;
 use crate::traits::RenderObject;
 use crate::data_structures::Ray;
 use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Color;
 use crate::shapes::Bounds;
+use crate::maths::gamma;
 
 pub struct Plane {
     position: Vector3,
@@ -18,19 +20,26 @@ impl RenderObject for Plane {
         if self.normal * ray.direction == 0.0 { return None; }
 
         let distance = ((self.position - ray.origin) * self.normal) / (self.normal * ray.direction);
-        if distance <= 0.0 { return None; }
+        if !ray.in_range(distance) { return None; }
 
+        // Project the hit back onto the plane to bound its error
         let position = ray.at(distance);
+        let position = position - self.normal * ((position - self.position) * self.normal);
+        let error = (position.abs() + self.position.abs()) * gamma(7);
         let a_dist = (position - self.position) * self.a_basis;
         let u = if a_dist >= 0.0 { a_dist % 1.0 } else { a_dist % 1.0 + 1.0 };
         let b_dist = (position - self.position) * self.b_basis;
         let v = if b_dist >= 0.0 { b_dist % 1.0 } else { b_dist % 1.0 + 1.0 };
-        Some(IntersectionPayload { position, distance, normal: self.normal.clone(), material_id: self.material_id, u, v })
+        Some(IntersectionPayload { position, distance, normal: self.normal, material_id: self.material_id, u, v, geometric_normal: self.normal, error, vertex_color: Color (1.0, 1.0, 1.0, 1.0) })
     }
 
     fn bounds(&self) -> Bounds {
         Bounds::Full
     }
+
+    fn material_id(&self) -> usize {
+        self.material_id
+    }
 }
 
 impl Plane {
@@ -2,7 +2,11 @@ use crate::traits::RenderObject;
 use crate::maths::Vector3;
 use crate::data_structures::Ray;
 use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Color;
 use crate::shapes::Bounds;
+use crate::maths::Matrix4x4;
+use crate::maths::random;
+use crate::maths::gamma;
 use core::f64::consts::PI;
 
 #[derive(Debug)]
@@ -17,11 +21,18 @@ impl RenderObject for Sphere {
         let discriminant = (ray.direction * (ray.origin - self.center)).powi(2) - ((ray.origin - self.center).square_magnitude() - self.radius.powi(2));
         if discriminant < 0.0 { return None; }
 
-        let distance = -(ray.direction * (ray.origin - self.center)) - discriminant.sqrt();
-        if distance <= 0.0 { return None; }
+        // Take the nearer root in range, which is the far one when the ray starts inside the sphere
+        let half_b = ray.direction * (ray.origin - self.center);
+        let near = -half_b - discriminant.sqrt();
+        let far = -half_b + discriminant.sqrt();
+        let distance = if ray.in_range(near) { near } else if ray.in_range(far) { far } else { return None; };
 
-        let normal = (ray.at(distance) - self.center) / self.radius;
-        let position = ray.at(distance);
+        // Project the hit back onto the sphere, which leaves only a small error relative to the center
+        let local = ray.at(distance) - self.center;
+        let local = local * (self.radius / local.magnitude());
+        let position = self.center + local;
+        let normal = local / self.radius;
+        let error = local.abs() * gamma(5) + position.abs() * gamma(1);
 
         let theta = f64::acos(-normal.2);
         let phi = f64::atan2(-normal.1, normal.0);
@@ -29,13 +40,50 @@ impl RenderObject for Sphere {
         let u = phi / (2.0 * PI);
         let v = theta / PI;
 
-        Some(IntersectionPayload { position, distance, normal, material_id: self.material_id, u, v })
+        Some(IntersectionPayload { position, distance, normal, material_id: self.material_id, u, v, geometric_normal: normal, error, vertex_color: Color (1.0, 1.0, 1.0, 1.0) })
     }
 
     fn bounds(&self) -> Bounds {
         let offset = Vector3 (self.radius, self.radius, self.radius);
         Bounds::BoundingBox (self.center - offset, self.center + offset)
     }
+
+    fn material_id(&self) -> usize {
+        self.material_id
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, ray: Ray) -> f64 {
+        if self.intersect(&ray).is_none() { return 0.0; }
+
+        let square_distance = (self.center - ray.origin).square_magnitude();
+        if square_distance <= self.radius.powi(2) { return 1.0 / (4.0 * PI); }
+
+        let cos_theta_max = (1.0 - self.radius.powi(2) / square_distance).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn random(&self, origin: Vector3) -> Vector3 {
+        let direction = self.center - origin;
+        let square_distance = direction.square_magnitude();
+        if square_distance <= self.radius.powi(2) { return Vector3::random_unit(); }
+
+        // Sample uniformly within the cone of directions subtended by the sphere
+        let cos_theta_max = (1.0 - self.radius.powi(2) / square_distance).sqrt();
+        let z = 1.0 + random::<f64>() * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * random::<f64>();
+        let sin_theta = (1.0 - z * z).sqrt();
+
+        let basis = Matrix4x4::from_i_basis(direction.normalise());
+        basis.transform(&Vector3 (z, phi.cos() * sin_theta, phi.sin() * sin_theta), false)
+    }
+
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius.powi(2)
+    }
 }
 
 impl Sphere {
@@ -43,3 +91,26 @@ impl Sphere {
         Box::new(Sphere { center, radius, material_id })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn honours_ray_range() {
+        let sphere = Sphere::new(Vector3 (0.0, 0.0, 0.0), 1.0, 0);
+        let mut ray = Ray::new(Vector3 (0.0, 0.0, -3.0), Vector3 (0.0, 0.0, 1.0));
+        assert!((sphere.intersect(&ray).unwrap().distance - 2.0).abs() < 1e-12);
+
+        // Starting past the front of the sphere finds the back
+        ray.t_min = 2.5;
+        assert!((sphere.intersect(&ray).unwrap().distance - 4.0).abs() < 1e-12);
+
+        ray.t_min = 0.0;
+        ray.t_max = 1.5;
+        assert!(sphere.intersect(&ray).is_none());
+
+        let inside = Ray::new(Vector3 (0.0, 0.0, 0.0), Vector3 (1.0, 0.0, 0.0));
+        assert!((sphere.intersect(&inside).unwrap().distance - 1.0).abs() < 1e-12);
+    }
+}
@@ -2,7 +2,10 @@ use crate::maths::Vector3;
 use crate::traits::RenderObject;
 use crate::data_structures::Ray;
 use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Color;
 use crate::shapes::Bounds;
+use crate::maths::random;
+use crate::maths::gamma;
 
 pub struct Triangle {
     a: Vector3,
@@ -24,7 +27,7 @@ impl RenderObject for Triangle {
         let d = -(n * self.a);
         let t = -(n * ray.origin + d) / (n * ray.direction);
 
-        if t < 0.0 { return None; }
+        if !ray.in_range(t) { return None; }
         let p = ray.at(t);
         
         let test_ab = n * Vector3::cross(&(self.b - self.a), &(p - self.a));
@@ -32,7 +35,13 @@ impl RenderObject for Triangle {
         let test_ca = n * Vector3::cross(&(self.a - self.c), &(p - self.c));
 
         if test_ab < 0.0 || test_cb < 0.0 || test_ca < 0.0 { return None; }
-        Some(IntersectionPayload { position: p, distance: t, normal: self.normal, material_id: self.material_id, u: 0.0, v: 0.0 })
+
+        // Rebuild the point from barycentric coordinates, which bounds how far it is from the plane
+        let square_area = n * n;
+        let (b0, b1, b2) = (test_cb / square_area, test_ca / square_area, test_ab / square_area);
+        let position = self.a * b0 + self.b * b1 + self.c * b2;
+        let error = ((self.a * b0).abs() + (self.b * b1).abs() + (self.c * b2).abs()) * gamma(7);
+        Some(IntersectionPayload { position, distance: t, normal: self.normal, material_id: self.material_id, u: 0.0, v: 0.0, geometric_normal: self.normal, error, vertex_color: Color (1.0, 1.0, 1.0, 1.0) })
     }
 
     fn bounds(&self) -> Bounds {
@@ -40,6 +49,41 @@ impl RenderObject for Triangle {
         let bound_min = Vector3 ( self.a.0.min(self.b.0).min(self.c.0), self.a.1.min(self.b.1).min(self.c.1), self.a.2.min(self.b.2).min(self.c.2) );
         Bounds::BoundingBox(bound_min, bound_max)
     }
+
+    fn material_id(&self) -> usize {
+        self.material_id
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, ray: Ray) -> f64 {
+        match self.intersect(&ray) {
+            None => 0.0,
+            Some(payload) => {
+                let square_distance = payload.distance.powi(2);
+                let cosine = (ray.direction * payload.normal).abs();
+
+                if cosine == 0.0 { return 0.0; }
+
+                square_distance / (cosine * self.area())
+            }
+        }
+    }
+
+    fn random(&self, origin: Vector3) -> Vector3 {
+        // Uniform barycentric coordinates over the triangle
+        let r1 = random::<f64>().sqrt();
+        let r2 = random::<f64>();
+        let point = self.a * (1.0 - r1) + self.b * (r1 * (1.0 - r2)) + self.c * (r1 * r2);
+
+        (point - origin).normalise()
+    }
+
+    fn area(&self) -> f64 {
+        Vector3::cross(&(self.b - self.a), &(self.c - self.a)).magnitude() / 2.0
+    }
 }
 
 impl Triangle {
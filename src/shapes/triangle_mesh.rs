@@ -0,0 +1,197 @@
+use crate::maths::Vector3;
+use crate::maths::random;
+use crate::maths::gamma;
+use crate::traits::RenderObject;
+use crate::data_structures::Ray;
+use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Color;
+use crate::shapes::Bounds;
+use std::sync::Arc;
+
+// Indexed triangle mesh. Normals, texture coordinates and colours are optional, but when present there
+// is one per position
+pub struct TriangleMesh {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    uvs: Vec<(f64, f64)>,
+    colors: Vec<Color>,
+    indices: Vec<[u32; 3]>,
+    material_ids: Vec<usize>,
+}
+
+impl TriangleMesh {
+    pub fn new(positions: Vec<Vector3>, normals: Vec<Vector3>, uvs: Vec<(f64, f64)>, colors: Vec<Color>, indices: Vec<[u32; 3]>, material_ids: Vec<usize>) -> Arc<TriangleMesh> {
+        assert!(normals.is_empty() || normals.len() == positions.len(), "TriangleMesh needs one normal per position");
+        assert!(uvs.is_empty() || uvs.len() == positions.len(), "TriangleMesh needs one uv per position");
+        assert!(colors.is_empty() || colors.len() == positions.len(), "TriangleMesh needs one color per position");
+        assert_eq!(indices.len(), material_ids.len(), "TriangleMesh needs one material id per triangle");
+        Arc::new(TriangleMesh { positions, normals, uvs, colors, indices, material_ids })
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    // One render object per triangle, each referencing the shared mesh
+    pub fn triangles(mesh: &Arc<TriangleMesh>) -> Vec<Box<dyn RenderObject>> {
+        (0..mesh.indices.len() as u32)
+            .map(|index| Box::new(MeshTriangle { mesh: Arc::clone(mesh), index }) as Box<dyn RenderObject>)
+            .collect()
+    }
+
+    fn vertices(&self, index: u32) -> [Vector3; 3] {
+        self.indices[index as usize].map(|i| self.positions[i as usize])
+    }
+}
+
+pub struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    index: u32,
+}
+
+impl MeshTriangle {
+    fn geometric_normal(&self) -> Vector3 {
+        let [a, b, c] = self.mesh.vertices(self.index);
+        Vector3::cross(&(b - a), &(c - a)).normalise()
+    }
+}
+
+impl RenderObject for MeshTriangle {
+    fn intersect(&self, ray: &Ray) -> Option<IntersectionPayload> {
+        let [a, b, c] = self.mesh.vertices(self.index);
+        let ab = b - a;
+        let ac = c - a;
+
+        // Moller-Trumbore, solving for the distance and barycentric coordinates together
+        let p = Vector3::cross(&ray.direction, &ac);
+        let determinant = ab * p;
+        if determinant == 0.0 { return None; }
+        let inverse_determinant = 1.0 / determinant;
+
+        let s = ray.origin - a;
+        let b1 = (s * p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&b1) { return None; }
+
+        let q = Vector3::cross(&s, &ab);
+        let b2 = (ray.direction * q) * inverse_determinant;
+        if b2 < 0.0 || b1 + b2 > 1.0 { return None; }
+
+        let distance = (ac * q) * inverse_determinant;
+        if !ray.in_range(distance) { return None; }
+        let b0 = 1.0 - b1 - b2;
+
+        let indices = self.mesh.indices[self.index as usize].map(|i| i as usize);
+        let normal = if self.mesh.normals.is_empty() {
+            Vector3::cross(&ab, &ac).normalise()
+        } else {
+            let [n0, n1, n2] = indices.map(|i| self.mesh.normals[i]);
+            (n0 * b0 + n1 * b1 + n2 * b2).normalise()
+        };
+        let (u, v) = if self.mesh.uvs.is_empty() {
+            (b1, b2)
+        } else {
+            let [t0, t1, t2] = indices.map(|i| self.mesh.uvs[i]);
+            (t0.0 * b0 + t1.0 * b1 + t2.0 * b2, t0.1 * b0 + t1.1 * b1 + t2.1 * b2)
+        };
+        let vertex_color = if self.mesh.colors.is_empty() {
+            Color (1.0, 1.0, 1.0, 1.0)
+        } else {
+            let [c0, c1, c2] = indices.map(|i| self.mesh.colors[i]);
+            Color (c0.0 * b0 + c1.0 * b1 + c2.0 * b2, c0.1 * b0 + c1.1 * b1 + c2.1 * b2, c0.2 * b0 + c1.2 * b1 + c2.2 * b2, 1.0)
+        };
+
+        // Rebuild the point from barycentric coordinates, which bounds how far it is from the plane
+        let position = a * b0 + b * b1 + c * b2;
+        let error = ((a * b0).abs() + (b * b1).abs() + (c * b2).abs()) * gamma(7);
+        let geometric_normal = Vector3::cross(&ab, &ac).normalise();
+        Some(IntersectionPayload { position, distance, normal, material_id: self.material_id(), u, v, geometric_normal, error, vertex_color })
+    }
+
+    fn bounds(&self) -> Bounds {
+        let [a, b, c] = self.mesh.vertices(self.index);
+        Bounds::BoundingBox(Vector3::min(&Vector3::min(&a, &b), &c), Vector3::max(&Vector3::max(&a, &b), &c))
+    }
+
+    fn material_id(&self) -> usize {
+        self.mesh.material_ids[self.index as usize]
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, ray: Ray) -> f64 {
+        match self.intersect(&ray) {
+            None => 0.0,
+            Some(payload) => {
+                let square_distance = payload.distance.powi(2);
+                let cosine = (ray.direction * self.geometric_normal()).abs();
+
+                if cosine == 0.0 { return 0.0; }
+
+                square_distance / (cosine * self.area())
+            }
+        }
+    }
+
+    fn random(&self, origin: Vector3) -> Vector3 {
+        let [a, b, c] = self.mesh.vertices(self.index);
+        let r1 = random::<f64>().sqrt();
+        let r2 = random::<f64>();
+        let point = a * (1.0 - r1) + b * (r1 * (1.0 - r2)) + c * (r1 * r2);
+
+        (point - origin).normalise()
+    }
+
+    fn area(&self) -> f64 {
+        let [a, b, c] = self.mesh.vertices(self.index);
+        Vector3::cross(&(b - a), &(c - a)).magnitude() / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad() -> Arc<TriangleMesh> {
+        let positions = vec![Vector3 (0.0, 0.0, 0.0), Vector3 (1.0, 0.0, 0.0), Vector3 (1.0, 1.0, 0.0), Vector3 (0.0, 1.0, 0.0)];
+        let normals = vec![Vector3 (-1.0, 0.0, 1.0).normalise(), Vector3 (1.0, 0.0, 1.0).normalise(), Vector3 (1.0, 0.0, 1.0).normalise(), Vector3 (-1.0, 0.0, 1.0).normalise()];
+        let uvs = vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        let colors = vec![Color (0.0, 0.0, 0.0, 1.0), Color (1.0, 0.0, 0.0, 1.0), Color (1.0, 1.0, 0.0, 1.0), Color (0.0, 1.0, 0.0, 1.0)];
+        TriangleMesh::new(positions, normals, uvs, colors, vec![[0, 1, 2], [0, 2, 3]], vec![4, 5])
+    }
+
+    #[test]
+    fn interpolates_attributes() {
+        let triangles = TriangleMesh::triangles(&quad());
+        assert_eq!(triangles.len(), 2);
+
+        let ray = Ray::new(Vector3 (0.75, 0.25, 1.0), Vector3 (0.0, 0.0, -1.0));
+        let payload = triangles[0].intersect(&ray).unwrap();
+        assert!((payload.distance - 1.0).abs() < 1e-12);
+        assert!((payload.u - 1.5).abs() < 1e-12);
+        assert!((payload.v - 0.5).abs() < 1e-12);
+        assert_eq!(payload.material_id, 4);
+        assert!((payload.vertex_color.0 - 0.75).abs() < 1e-12);
+        assert!((payload.vertex_color.1 - 0.25).abs() < 1e-12);
+
+        // The shading normal leans towards the vertices on the right
+        assert!(payload.normal.0 > 0.0);
+        assert!((payload.normal.magnitude() - 1.0).abs() < 1e-12);
+
+        assert!(triangles[1].intersect(&ray).is_none());
+    }
+
+    #[test]
+    fn area_and_bounds() {
+        let triangles = TriangleMesh::triangles(&quad());
+        assert!((triangles[1].area() - 0.5).abs() < 1e-12);
+        match triangles[1].bounds() {
+            Bounds::BoundingBox(min, max) => {
+                assert_eq!(min, Vector3 (0.0, 0.0, 0.0));
+                assert_eq!(max, Vector3 (1.0, 1.0, 0.0));
+            },
+            Bounds::Full => panic!("triangle bounds should be finite"),
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use crate::traits::RenderObject;
 use crate::data_structures::Ray;
 use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Color;
 use crate::maths::Vector3;
 use crate::shapes::Bounds;
-use rand::random;
+use crate::maths::random;
+use crate::maths::gamma;
 
 pub struct XYRect {
     x0: f64,
@@ -17,26 +19,36 @@ pub struct XYRect {
 impl RenderObject for XYRect {
     fn intersect(&self, ray: &Ray) -> Option<IntersectionPayload> {
         let t = (self.z - ray.origin.2) / ray.direction.2;
-        if t < 0.0 { return None; }
+        if !ray.in_range(t) { return None; }
         let x = ray.origin.0 + t * ray.direction.0;
         let y = ray.origin.1 + t * ray.direction.1;
         if x < self.x0 || x > self.x1 || y < self.y0 || y > self.y1 { return None; }
-        let position = ray.at(t);
+        // The fixed coordinate is exact, so there is no error along the normal
+        let position = Vector3 (x, y, self.z);
+        let error = Vector3 (x.abs() * gamma(3), y.abs() * gamma(3), 0.0);
         let u = (x - self.x0) / (self.x1 - self.x0);
         let v = (y - self.y0) / (self.y1 - self.y0);
         let normal = Vector3 (0.0, 0.0, 1.0);
-        Some(IntersectionPayload { distance: t, position, normal, material_id: self.material_id, u, v})
+        Some(IntersectionPayload { distance: t, position, normal, material_id: self.material_id, u, v, geometric_normal: normal, error, vertex_color: Color (1.0, 1.0, 1.0, 1.0) })
     }
 
     fn bounds(&self) -> Bounds {
         Bounds::BoundingBox(Vector3 (self.x0, self.y0, self.z - 1e-5), Vector3 (self.x1, self.y1, self.z + 1e-5))
     }
 
+    fn material_id(&self) -> usize {
+        self.material_id
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
     fn pdf_value(&self, ray: Ray) -> f64 {
         match self.intersect(&ray) {
             None => 0.0,
             Some(payload) => {
-                let area = (self.x1 - self.x0) * (self.y1 - self.y0);
+                let area = self.area();
                 let square_distance = payload.distance.powi(2);
                 let cosine = (ray.direction * payload.normal).abs();
 
@@ -53,6 +65,10 @@ impl RenderObject for XYRect {
 
         (Vector3 (x, y, self.z) - origin).normalise()
     }
+
+    fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
 }
 
 impl XYRect {
@@ -1,8 +1,11 @@
 use crate::traits::RenderObject;
 use crate::data_structures::Ray;
 use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Color;
 use crate::maths::Vector3;
 use crate::shapes::Bounds;
+use crate::maths::random;
+use crate::maths::gamma;
 
 pub struct XZRect {
     x0: f64,
@@ -16,20 +19,56 @@ pub struct XZRect {
 impl RenderObject for XZRect {
     fn intersect(&self, ray: &Ray) -> Option<IntersectionPayload> {
         let t = (self.y - ray.origin.1) / ray.direction.1;
-        if t < 0.0 { return None; }
+        if !ray.in_range(t) { return None; }
         let x = ray.origin.0 + t * ray.direction.0;
         let z = ray.origin.2 + t * ray.direction.2;
         if x < self.x0 || x > self.x1 || z < self.z0 || z > self.z1 { return None; }
-        let position = ray.at(t);
+        // The fixed coordinate is exact, so there is no error along the normal
+        let position = Vector3 (x, self.y, z);
+        let error = Vector3 (x.abs() * gamma(3), 0.0, z.abs() * gamma(3));
         let u = (x - self.x0) / (self.x1 - self.x0);
         let v = (z - self.z0) / (self.z1 - self.z0);
         let normal = Vector3 (0.0, 1.0, 0.0);
-        Some(IntersectionPayload { distance: t, position, normal, material_id: self.material_id, u, v})
+        Some(IntersectionPayload { distance: t, position, normal, material_id: self.material_id, u, v, geometric_normal: normal, error, vertex_color: Color (1.0, 1.0, 1.0, 1.0) })
     }
 
     fn bounds(&self) -> Bounds {
         Bounds::BoundingBox(Vector3 (self.x0, self.y - 1e-5, self.z0), Vector3 (self.x1, self.y + 1e-5, self.z1))
     }
+
+    fn material_id(&self) -> usize {
+        self.material_id
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, ray: Ray) -> f64 {
+        match self.intersect(&ray) {
+            None => 0.0,
+            Some(payload) => {
+                let area = self.area();
+                let square_distance = payload.distance.powi(2);
+                let cosine = (ray.direction * payload.normal).abs();
+
+                if cosine == 0.0 { return 0.0; }
+
+                square_distance / (cosine * area)
+            }
+        }
+    }
+
+    fn random(&self, origin: Vector3) -> Vector3 {
+        let x = random::<f64>() * (self.x1 - self.x0) + self.x0;
+        let z = random::<f64>() * (self.z1 - self.z0) + self.z0;
+
+        (Vector3 (x, self.y, z) - origin).normalise()
+    }
+
+    fn area(&self) -> f64 {
+        (self.x1 - self.x0) * (self.z1 - self.z0)
+    }
 }
 
 impl XZRect {
@@ -1,9 +1,11 @@
 use crate::traits::RenderObject;
 use crate::data_structures::Ray;
 use crate::data_structures::IntersectionPayload;
+use crate::data_structures::Color;
 use crate::maths::Vector3;
 use crate::shapes::Bounds;
-use rand::random;
+use crate::maths::random;
+use crate::maths::gamma;
 
 pub struct YZRect {
     y0: f64,
@@ -17,26 +19,36 @@ pub struct YZRect {
 impl RenderObject for YZRect {
     fn intersect(&self, ray: &Ray) -> Option<IntersectionPayload> {
         let t = (self.x - ray.origin.0) / ray.direction.0;
-        if t < 0.0 { return None; }
+        if !ray.in_range(t) { return None; }
         let y = ray.origin.1 + t * ray.direction.1;
         let z = ray.origin.2 + t * ray.direction.2;
         if y < self.y0 || y > self.y1 || z < self.z0 || z > self.z1 { return None; }
-        let position = ray.at(t);
+        // The fixed coordinate is exact, so there is no error along the normal
+        let position = Vector3 (self.x, y, z);
+        let error = Vector3 (0.0, y.abs() * gamma(3), z.abs() * gamma(3));
         let u = (y - self.y0) / (self.y1 - self.y0);
         let v = (z - self.z0) / (self.z1 - self.z0);
         let normal = Vector3 (1.0, 0.0, 0.0);
-        Some(IntersectionPayload { distance: t, position, normal, material_id: self.material_id, u, v})
+        Some(IntersectionPayload { distance: t, position, normal, material_id: self.material_id, u, v, geometric_normal: normal, error, vertex_color: Color (1.0, 1.0, 1.0, 1.0) })
     }
 
     fn bounds(&self) -> Bounds {
         Bounds::BoundingBox(Vector3 (self.x - 1e-5, self.y0, self.z0), Vector3 (self.x + 1e-5, self.y1, self.z1))
     }
 
+    fn material_id(&self) -> usize {
+        self.material_id
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
     fn pdf_value(&self, ray: Ray) -> f64 {
         match self.intersect(&ray) {
             None => 0.0,
             Some(payload) => {
-                let area = (self.y1 - self.y0) * (self.z1 - self.z0);
+                let area = self.area();
                 let square_distance = payload.distance.powi(2);
                 let cosine = (ray.direction * payload.normal).abs();
 
@@ -53,6 +65,10 @@ impl RenderObject for YZRect {
 
         (Vector3 (self.x, y, z) - origin).normalise()
     }
+
+    fn area(&self) -> f64 {
+        (self.y1 - self.y0) * (self.z1 - self.z0)
+    }
 }
 
 impl YZRect {
@@ -0,0 +1,149 @@
+use crate::traits::Texture;
+use crate::data_structures::Color;
+use crate::data_structures::Image;
+use crate::maths::Vector3;
+use std::sync::Arc;
+
+// How texture coordinates outside 0..1 map back onto the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+pub struct ImageTexture {
+    image: Arc<Image>,
+    wrap: WrapMode,
+    filter: Filter,
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Vector3) -> Color {
+        // Degenerate coordinates, for example from a collapsed mesh triangle, read the corner texel
+        let (u, v) = (if u.is_finite() { u } else { 0.0 }, if v.is_finite() { v } else { 0.0 });
+        // v = 0 is the bottom of the image, while image rows run from the top down
+        let x = u * self.image.width as f64;
+        let y = (1.0 - v) * self.image.height as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // Interpolate between the four nearest pixel centres
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                // Huge coordinates saturate the cast, so the next texel must not overflow
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let (x1, y1) = (x0.saturating_add(1), y0.saturating_add(1));
+
+                let top = lerp(self.texel(x0, y0), self.texel(x1, y0), tx);
+                let bottom = lerp(self.texel(x0, y1), self.texel(x1, y1), tx);
+                lerp(top, bottom, ty)
+            },
+        }
+    }
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Image>, wrap: WrapMode, filter: Filter) -> Box<ImageTexture> {
+        assert!(image.width > 0 && image.height > 0, "ImageTexture needs a non-empty image");
+        Box::new(ImageTexture { image, wrap, filter })
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        self.image.get_pixel(self.wrap(x, self.image.width), self.wrap(y, self.image.height))
+    }
+
+    fn wrap(&self, index: i64, size: usize) -> usize {
+        let size = size as i64;
+        let wrapped = match self.wrap {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::Clamp => index.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let index = index.rem_euclid(2 * size);
+                if index < size { index } else { 2 * size - 1 - index }
+            },
+        };
+        wrapped as usize
+    }
+}
+
+// Colour's arithmetic resets alpha, so blend every channel here
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    Color (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t, a.2 + (b.2 - a.2) * t, a.3 + (b.3 - a.3) * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 2x1 image, black on the left and white on the right
+    fn texture(wrap: WrapMode, filter: Filter) -> Box<ImageTexture> {
+        let mut image = Image::new(2, 1);
+        image.set_pixel(0, 0, &Color (0.0, 0.0, 0.0, 1.0));
+        ImageTexture::new(Arc::new(image), wrap, filter)
+    }
+
+    #[test]
+    fn nearest_wrap_modes() {
+        let p = Vector3 (0.0, 0.0, 0.0);
+        let repeat = texture(WrapMode::Repeat, Filter::Nearest);
+        assert_eq!(repeat.value(0.25, 0.5, p).0, 0.0);
+        assert_eq!(repeat.value(1.25, 0.5, p).0, 0.0);
+        assert_eq!(repeat.value(-0.25, 0.5, p).0, 1.0);
+
+        let clamp = texture(WrapMode::Clamp, Filter::Nearest);
+        assert_eq!(clamp.value(-3.0, 0.5, p).0, 0.0);
+        assert_eq!(clamp.value(5.0, 0.5, p).0, 1.0);
+
+        let mirror = texture(WrapMode::Mirror, Filter::Nearest);
+        assert_eq!(mirror.value(1.25, 0.5, p).0, 1.0);
+        assert_eq!(mirror.value(1.75, 0.5, p).0, 0.0);
+        assert_eq!(mirror.value(-0.25, 0.5, p).0, 0.0);
+    }
+
+    #[test]
+    fn bilinear_blends_between_centres() {
+        let p = Vector3 (0.0, 0.0, 0.0);
+        let clamp = texture(WrapMode::Clamp, Filter::Bilinear);
+        assert_eq!(clamp.value(0.25, 0.5, p).0, 0.0);
+        assert_eq!(clamp.value(0.5, 0.5, p).0, 0.5);
+        assert_eq!(clamp.value(0.625, 0.5, p).0, 0.75);
+        assert_eq!(clamp.value(0.5, 0.5, p).3, 1.0);
+
+        // Repeating blends the right edge back into the left
+        let repeat = texture(WrapMode::Repeat, Filter::Bilinear);
+        assert_eq!(repeat.value(0.0, 0.5, p).0, 0.5);
+    }
+
+    #[test]
+    fn degenerate_coordinates() {
+        let p = Vector3 (0.0, 0.0, 0.0);
+        for wrap in [WrapMode::Repeat, WrapMode::Clamp, WrapMode::Mirror] {
+            for filter in [Filter::Nearest, Filter::Bilinear] {
+                let texture = texture(wrap, filter);
+                for (u, v) in [(f64::INFINITY, 0.5), (0.5, f64::NEG_INFINITY), (f64::NAN, f64::NAN), (1e300, -1e300)] {
+                    let color = texture.value(u, v, p);
+                    assert!((0.0..=1.0).contains(&color.0), "{:?} {:?} {} {}", wrap, filter, u, v);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn v_runs_bottom_to_top() {
+        let mut image = Image::new(1, 2);
+        image.set_pixel(0, 1, &Color (0.0, 0.0, 0.0, 1.0));
+        let texture = ImageTexture::new(Arc::new(image), WrapMode::Clamp, Filter::Nearest);
+        let p = Vector3 (0.0, 0.0, 0.0);
+        assert_eq!(texture.value(0.5, 0.25, p).0, 0.0);
+        assert_eq!(texture.value(0.5, 0.75, p).0, 1.0);
+    }
+}
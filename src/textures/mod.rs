@@ -1,6 +1,9 @@
 mod constant_texture;
 mod checked_texture;
+mod image_texture;
 
 pub use constant_texture::ConstantTexture;
 pub use checked_texture::CheckedTexture;
-
+pub use image_texture::ImageTexture;
+pub use image_texture::WrapMode;
+pub use image_texture::Filter;
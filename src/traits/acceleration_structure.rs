@@ -0,0 +1,12 @@
+use crate::data_structures::Ray;
+use crate::data_structures::IntersectionPayload;
+use crate::traits::RenderObject;
+
+// Spatial index over a scene's render objects. The objects are owned by the scene and passed in on
+// every query, the structure only stores indices into them
+pub trait AccelerationStructure: Send + Sync {
+    // Closest hit, along with the index of the render object that was hit
+    fn intersect(&self, render_objects: &[Box<dyn RenderObject>], ray: &Ray) -> Option<(usize, IntersectionPayload)>;
+    // Any hit query, which may return as soon as one object blocks the ray
+    fn occluded(&self, render_objects: &[Box<dyn RenderObject>], ray: &Ray) -> bool;
+}
@@ -1,5 +1,5 @@
 use crate::data_structures::Ray;
 
-pub trait Camera {
+pub trait Camera: Send + Sync {
     fn generate_ray(&self, width: usize, height: usize, x: f64, y: f64) -> Ray;
 }
@@ -3,8 +3,13 @@ use crate::data_structures::IntersectionPayload;
 use crate::data_structures::Color;
 use crate::data_structures::ScatterPayload;
 
-pub trait Material {
-    fn emmission(&self, payload: &IntersectionPayload) -> Color;
+pub trait Material: Send + Sync {
+    fn emmission(&self, payload: &IntersectionPayload, incoming_direction: Vector3) -> Color;
+
+    // Average emitted radiance, used to decide how often a light is sampled. Zero for materials that do not emit
+    fn emitted_power(&self) -> f64 {
+        0.0
+    }
 
     fn scatter(&self, payload: &IntersectionPayload, incoming_direction: Vector3) -> Option<ScatterPayload>;
 
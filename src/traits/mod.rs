@@ -4,6 +4,7 @@ mod camera;
 mod material;
 mod texture;
 mod sampler;
+mod acceleration_structure;
 
 pub use render_object::RenderObject;
 pub use transformable::Transformable;
@@ -11,3 +12,4 @@ pub use camera::Camera;
 pub use material::Material;
 pub use texture::Texture;
 pub use sampler::Sampler;
+pub use acceleration_structure::AccelerationStructure;
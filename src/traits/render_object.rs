@@ -3,9 +3,21 @@ use crate::data_structures::IntersectionPayload;
 use crate::shapes::Bounds;
 use crate::maths::Vector3;
 
-pub trait RenderObject {
+pub trait RenderObject: Send + Sync {
     fn intersect(&self, ray: &Ray) -> Option<IntersectionPayload>;
     fn bounds(&self) -> Bounds;
+    fn material_id(&self) -> usize;
+
+    // Whether anything blocks the ray within its range. Shapes can stop at the first hit they find
+    fn occluded(&self, ray: &Ray) -> bool {
+        self.intersect(ray).is_some()
+    }
+
+    // Whether pdf_value, random and area are implemented, so the object can be sampled as a light.
+    // Shapes that implement them opt in
+    fn is_samplable(&self) -> bool {
+        false
+    }
 
     fn pdf_value(&self, _ray: Ray) -> f64 {
         panic!("Renderobject::pdf_value not implemented")
@@ -14,4 +26,8 @@ pub trait RenderObject {
     fn random(&self, _origin: Vector3) -> Vector3 {
         panic!("RenderObject::random not implemented")
     }
+
+    fn area(&self) -> f64 {
+        panic!("RenderObject::area not implemented")
+    }
 }
@@ -1,6 +1,6 @@
 use crate::data_structures::Color;
 use crate::maths::Vector3;
 
-pub trait Texture {
+pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: Vector3) -> Color;
 }